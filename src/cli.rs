@@ -1,10 +1,72 @@
 use arg::Args;
 
 use std::env;
+use core::str::FromStr;
+
+use crate::html::Ruby;
+use crate::render::Format;
+use crate::select::{Selection, Date};
 use core::num::NonZeroUsize;
 use std::process::ExitCode;
 
-pub fn default_from_value() -> NonZeroUsize {
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Novel reference, given as id or URL
+pub struct Novel {
+    ///Id of the work
+    pub id: String,
+    ///Id of the episode, if URL points to one
+    pub episode: Option<String>,
+}
+
+impl Novel {
+    #[inline(always)]
+    fn is_id(text: &str) -> bool {
+        !text.is_empty() && text.bytes().all(|byte| byte.is_ascii_digit())
+    }
+}
+
+impl FromStr for Novel {
+    type Err = ();
+
+    ///Accepts id or URL in form of `[https://]kakuyomu.jp/works/<id>[/episodes/<id>]`, including query and fragment
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if Self::is_id(text) {
+            return Ok(Self {
+                id: text.to_owned(),
+                episode: None,
+            });
+        }
+
+        let url = text.strip_prefix("https://").or_else(|| text.strip_prefix("http://")).unwrap_or(text);
+        let url = match url.find(|ch| ch == '?' || ch == '#') {
+            Some(idx) => &url[..idx],
+            None => url,
+        };
+        let mut parts = url.split('/').filter(|part| !part.is_empty());
+        match parts.next() {
+            Some(host) if host == "kakuyomu.jp" || host.ends_with(".kakuyomu.jp") => (),
+            _ => return Err(()),
+        }
+
+        match (parts.next(), parts.next()) {
+            (Some("works"), Some(id)) if Self::is_id(id) => match (parts.next(), parts.next()) {
+                (Some("episodes"), Some(episode)) if Self::is_id(episode) => Ok(Self {
+                    id: id.to_owned(),
+                    episode: Some(episode.to_owned()),
+                }),
+                (Some("episodes"), Some(_)) => Err(()),
+                _ => Ok(Self {
+                    id: id.to_owned(),
+                    episode: None,
+                }),
+            },
+            _ => Err(()),
+        }
+    }
+}
+
+pub fn default_jobs_value() -> NonZeroUsize {
     unsafe {
         core::num::NonZeroUsize::new_unchecked(1)
     }
@@ -13,21 +75,89 @@ pub fn default_from_value() -> NonZeroUsize {
 #[derive(Args, Debug)]
 ///Utility to download text of the kakuyomu novels
 pub struct Cli {
-    #[arg(long, default_value = "default_from_value()")]
+    #[arg(long)]
     ///Specify from which chapter to start dumping. Default: 1.
-    pub from: NonZeroUsize,
+    pub from: Option<NonZeroUsize>,
     #[arg(long)]
     ///Specify until which chapter to dump.
     pub to: Option<NonZeroUsize>,
     #[arg(long, short)]
-    ///Output file name. By default writes ./<title>.md
+    ///Chapters to dump, instead of --from/--to: comma separated numbers (15), ranges (1-10, 40-), last chapters (last:5), episode ids (id:<id>) or section titles (section:<title>).
+    pub select: Option<Selection>,
+    #[arg(long)]
+    ///Dump only chapters published on or after date (YYYY-MM-DD, JST).
+    pub since: Option<Date>,
+    #[arg(long)]
+    ///Dump only chapters published on or before date (YYYY-MM-DD, JST).
+    pub until: Option<Date>,
+    #[arg(long, short)]
+    ///Output file name, when dumping single novel. By default writes ./<title>.<format>
     pub out: Option<String>,
-    #[arg(required)]
-    ///Id of the novel to dump (e.g. 1177354054883819762)
-    pub novel: String,
+    #[arg(long, default_value = "Format::Markdown")]
+    ///Output format: md, epub, txt, aozora (青空文庫 notation, ignoring --ruby), json, ndjson (episode per line) or html (single page). Default: md.
+    pub format: Format,
+    #[arg(long, default_value = "Ruby::Html")]
+    ///Furigana rendering: html, paren (漢字（かんじ）), aozora (｜漢字《かんじ》) or strip. Default: html.
+    pub ruby: Ruby,
+    #[arg(long)]
+    ///Wrap lines of txt output at specified number of columns, counting full-width characters as 2.
+    pub wrap: Option<NonZeroUsize>,
+    #[arg(long)]
+    ///Encode aozora output in Shift_JIS with CRLF line endings.
+    pub sjis: bool,
+    #[arg(long)]
+    ///Use vertical writing (縦書き) by default in html output.
+    pub vertical: bool,
+    #[arg(long)]
+    ///Write directory with file per chapter, index and metadata. Unchanged files are not rewritten. By default writes ./<title>/
+    pub split: bool,
+    #[arg(long)]
+    ///Continue interrupted dump into existing output, using its state file (<out>.state).
+    pub resume: bool,
+    #[arg(long)]
+    ///Download only chapters missing from existing output (or its <out>.state file), placing them in reading order. Novel and output options are taken from the state.
+    pub update: Option<String>,
+    #[arg(long)]
+    ///Only print work information, without downloading chapters.
+    pub info: bool,
+    #[arg(long)]
+    ///Print work information as JSON, together with --info.
+    pub json: bool,
+    #[arg(long = "dry-run")]
+    ///Only fetch table of contents and list chapters, marking the ones that would be downloaded.
+    pub dry_run: bool,
+    #[arg(long, default_value = "default_jobs_value()")]
+    ///Number of chapters to download in parallel. Default: 1.
+    pub jobs: NonZeroUsize,
+    #[arg(long, default_value = "5")]
+    ///Limit of requests per second, 0 to disable. Default: 5.
+    pub rate: u32,
+    #[arg(long, default_value = "3")]
+    ///Number of retries for failed requests, using exponential backoff. Default: 3.
+    pub retries: u32,
+    #[arg(long)]
+    ///Exit with code 3, if any chapter could not be downloaded.
+    pub strict: bool,
+    #[arg(long)]
+    ///Store every received HTTP response (URL, status, headers and body) in directory, to replay it later.
+    pub record: Option<String>,
+    #[arg(long)]
+    ///Serve HTTP responses from directory written by --record, without network.
+    pub replay: Option<String>,
+    #[arg(long)]
+    ///File with list of novels to dump, one id or URL per line. Use '-' to read from stdin.
+    pub batch: Option<String>,
+    ///Ids or URLs of novels to dump (e.g. 1177354054883819762). Dump starts from the episode, if URL points to one.
+    pub novels: Vec<Novel>,
 }
 
 impl Cli {
+    #[inline(always)]
+    ///Returns whether standard output is reserved for JSON
+    pub fn is_quiet(&self) -> bool {
+        self.info && self.json
+    }
+
     #[inline]
     pub fn new() -> Option<Result<Self, ExitCode>> {
         let args: Vec<_> = env::args().skip(1).collect();
@@ -53,3 +183,47 @@ impl Cli {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Novel;
+
+    fn novel(id: &str, episode: Option<&str>) -> Result<Novel, ()> {
+        Ok(Novel {
+            id: id.to_owned(),
+            episode: episode.map(str::to_owned),
+        })
+    }
+
+    #[test]
+    fn should_parse_novel_id() {
+        assert_eq!(" 1177354054883819762 ".parse(), novel("1177354054883819762", None));
+        assert_eq!("".parse::<Novel>(), Err(()));
+        assert_eq!("12a".parse::<Novel>(), Err(()));
+    }
+
+    #[test]
+    fn should_parse_work_url() {
+        assert_eq!("https://kakuyomu.jp/works/100".parse(), novel("100", None));
+        assert_eq!("http://kakuyomu.jp/works/100/".parse(), novel("100", None));
+        assert_eq!("kakuyomu.jp/works/100".parse(), novel("100", None));
+        assert_eq!("https://kakuyomu.jp/works/100?utm_source=x#top".parse(), novel("100", None));
+        assert_eq!("https://www.kakuyomu.jp/works/100".parse(), novel("100", None));
+    }
+
+    #[test]
+    fn should_parse_episode_url() {
+        assert_eq!("https://kakuyomu.jp/works/100/episodes/200".parse(), novel("100", Some("200")));
+        assert_eq!("https://kakuyomu.jp/works/100/episodes/200?ref=x#end".parse(), novel("100", Some("200")));
+    }
+
+    #[test]
+    fn should_reject_foreign_url() {
+        assert_eq!("https://example.com/works/100".parse::<Novel>(), Err(()));
+        assert_eq!("https://notkakuyomu.jp/works/100".parse::<Novel>(), Err(()));
+        assert_eq!("https://kakuyomu.jp.example.com/works/100".parse::<Novel>(), Err(()));
+        assert_eq!("https://kakuyomu.jp/users/100".parse::<Novel>(), Err(()));
+        assert_eq!("https://kakuyomu.jp/works/abc".parse::<Novel>(), Err(()));
+        assert_eq!("https://kakuyomu.jp/works/100/episodes/abc".parse::<Novel>(), Err(()));
+    }
+}
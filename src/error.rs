@@ -0,0 +1,125 @@
+//!Crate errors
+use core::fmt;
+use std::{io, path};
+
+use crate::http;
+
+#[derive(Debug)]
+///Error of fetching or writing novel
+pub enum Error {
+    ///Work or episode does not exist
+    NotFound {
+        url: String,
+        episode: Option<String>,
+    },
+    ///Request failed
+    Network {
+        url: String,
+        episode: Option<String>,
+        error: http::Error,
+    },
+    ///Page lacks expected content, likely due to change of the site's layout
+    LayoutChanged {
+        url: String,
+        episode: Option<String>,
+        ///Description of missing content
+        missing: &'static str,
+    },
+    ///Page data cannot be parsed
+    Parse {
+        url: String,
+        error: serde_json::Error,
+    },
+    ///Recorded session cannot be accessed (e.g. response is missing from replay)
+    Session {
+        url: String,
+        error: io::Error,
+    },
+    ///Output cannot be written
+    Output {
+        ///Path to the output, if known
+        path: Option<path::PathBuf>,
+        error: io::Error,
+    },
+}
+
+impl Error {
+    #[inline]
+    ///Creates error from result of request to `url`
+    pub fn http(url: String, episode: Option<String>, error: http::Error) -> Self {
+        match error {
+            http::Error::StatusFailed(404) => Self::NotFound {
+                url,
+                episode,
+            },
+            http::Error::Session(error) => Self::Session {
+                url,
+                error,
+            },
+            error => Self::Network {
+                url,
+                episode,
+                error,
+            },
+        }
+    }
+
+    #[inline]
+    ///Creates output error
+    pub fn output(path: Option<&path::Path>, error: io::Error) -> Self {
+        Self::Output {
+            path: path.map(path::Path::to_path_buf),
+            error,
+        }
+    }
+
+    ///Returns URL of the page, that caused error
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            Self::NotFound { url, .. } | Self::Network { url, .. } | Self::LayoutChanged { url, .. } | Self::Parse { url, .. } | Self::Session { url, .. } => Some(url),
+            Self::Output { .. } => None,
+        }
+    }
+
+    ///Returns id of the episode, that caused error
+    pub fn episode(&self) -> Option<&str> {
+        match self {
+            Self::NotFound { episode, .. } | Self::Network { episode, .. } | Self::LayoutChanged { episode, .. } => episode.as_deref(),
+            Self::Parse { .. } | Self::Session { .. } | Self::Output { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound { url, episode: None } => write!(fmt, "{url}: No such novel found"),
+            Self::NotFound { url, episode: Some(_) } => write!(fmt, "{url}: No such episode found"),
+            Self::Network { url, error, .. } => write!(fmt, "{url}: {error}"),
+            Self::LayoutChanged { url, missing, .. } => write!(fmt, "{url}: Cannot find {missing}, site layout might have changed"),
+            Self::Parse { url, error } => write!(fmt, "{url}: Unable to parse page: {error}"),
+            Self::Session { url, error } => write!(fmt, "{url}: Unable to access recorded session: {error}"),
+            Self::Output { path: Some(path), error } => write!(fmt, "{}: Cannot write: {error}", path.display()),
+            Self::Output { path: None, error } => write!(fmt, "Cannot write: {error}"),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    #[inline]
+    fn from(error: io::Error) -> Self {
+        Self::output(None, error)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Network { error, .. } => Some(error),
+            Self::Parse { error, .. } => Some(error),
+            Self::Session { error, .. } => Some(error),
+            Self::Output { error, .. } => Some(error),
+            Self::NotFound { .. } | Self::LayoutChanged { .. } => None,
+        }
+    }
+}
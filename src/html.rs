@@ -1,66 +1,13 @@
 use core::fmt;
+use core::str::FromStr;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use core::ops::Range;
 
 use scraper::html::Html;
 use scraper::selector::Selector;
 use serde_ignored_type::IgnoredAny;
 
-pub struct Title<'a> {
-    pub name: &'a str,
-    pub author: Option<&'a str>,
-}
-
-impl<'a> Title<'a> {
-    pub fn new(mut title: &'a str) -> Self {
-        const AUTHOR_END: char = '）';
-        const AUTHOR_START: char = '（';
-
-        title = title.trim();
-        if let Some(stripped) = title.strip_suffix(" - カクヨム") {
-            title = stripped;
-        }
-        let author = match title.rfind(AUTHOR_END) {
-            Some(idx) => {
-                let mut author = &title[..idx];
-                //Make sure we have opening bracket
-                match author.rfind(AUTHOR_START) {
-                    Some(mut start_idx) => {
-                        //If author used brackets in his name, then we need to account for that
-                        //So we count number of round brackets
-                        let mut sub_end_count = 0usize;
-                        let mut author_sub = author;
-                        while let Some(nested_idx) = author_sub.rfind(AUTHOR_END) {
-                            sub_end_count = sub_end_count.saturating_add(1);
-                            author_sub = &author_sub[..nested_idx];
-                        }
-
-                        //if there is nested closing brackets, then skip equal number of opening brackets
-                        while sub_end_count > 0 {
-                            if let Some(new_idx) = author[..start_idx - AUTHOR_START.len_utf8()].rfind(AUTHOR_START) {
-                                sub_end_count -= 1;
-                                start_idx = new_idx;
-                            } else {
-                                break;
-                            }
-                        }
-                        author = &author[start_idx + AUTHOR_START.len_utf8()..];
-                        title = &title[..start_idx];
-
-                        Some(author)
-                    },
-                    None => None
-                }
-            },
-            None => None,
-        };
-
-        Self {
-            name: title,
-            author,
-        }
-    }
-}
-
 #[derive(Debug, serde_derive::Deserialize)]
 struct ScriptState {
     props: Props,
@@ -78,9 +25,69 @@ struct PageProps {
     __APOLLO_STATE__: ApolloState
 }
 
+#[derive(Debug, serde_derive::Deserialize)]
+struct Ref {
+    __ref: String,
+}
+
+#[derive(Debug, Default, serde_derive::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct WorkEntry {
+    id: String,
+    title: String,
+    author: Option<Ref>,
+    catchphrase: Option<String>,
+    introduction: Option<String>,
+    genre: Option<String>,
+    tag_labels: Vec<String>,
+    serial_status: Option<SerialStatus>,
+    total_character_count: Option<u64>,
+    public_episode_count: Option<u64>,
+    published_at: Option<String>,
+    last_episode_published_at: Option<String>,
+    total_review_point: Option<u64>,
+    total_followers: Option<u64>,
+    is_cruel: bool,
+    is_violent: bool,
+    is_sexual: bool,
+    table_of_contents: Vec<Ref>,
+}
+
+#[derive(Debug, serde_derive::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TableOfContentsChapterEntry {
+    chapter: Option<Ref>,
+    #[serde(default)]
+    episode_unions: Vec<Ref>,
+}
+
+#[derive(Debug, serde_derive::Deserialize)]
+struct ChapterEntry {
+    level: u8,
+    title: String,
+}
+
+#[derive(Debug, serde_derive::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EpisodeEntry {
+    id: String,
+    title: Option<String>,
+    published_at: Option<String>,
+    character_count: Option<u64>,
+}
+
+#[derive(Debug, serde_derive::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UserAccountEntry {
+    activity_name: Option<String>,
+    name: Option<String>,
+}
+
 #[derive(Debug)]
 struct ApolloState {
-    chapters: Vec<String>,
+    work: Work,
+    sections: Vec<Section>,
+    chapters: Vec<Episode>,
 }
 
 struct ApolloStateVisitor;
@@ -93,17 +100,126 @@ impl<'de> serde::de::Visitor<'de> for ApolloStateVisitor {
     }
 
     fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
-        const SUFFIX: &str = "Episode:";
+        const EPISODE: &str = "Episode:";
+        const TOC_CHAPTER: &str = "TableOfContentsChapter:";
+        const CHAPTER: &str = "Chapter:";
+        const WORK: &str = "Work:";
+        const USER: &str = "UserAccount:";
+        const ROOT_QUERY: &str = "ROOT_QUERY";
+        const ROOT_WORK: &str = "work(";
 
-        let mut chapters = Vec::new();
+        let mut episodes = HashMap::new();
+        let mut toc_chapters = HashMap::new();
+        let mut sections = HashMap::new();
+        let mut works = HashMap::new();
+        let mut users = HashMap::new();
+        let mut root_work = None;
         while let Some(entry) = map.next_key::<Cow<'de, str>>()? {
-            if let Some(chapter) = entry.strip_prefix(SUFFIX) {
-                chapters.push(chapter.to_owned());
+            if entry.starts_with(EPISODE) {
+                episodes.insert(entry.into_owned(), map.next_value::<EpisodeEntry>()?);
+            } else if entry.starts_with(TOC_CHAPTER) {
+                toc_chapters.insert(entry.into_owned(), map.next_value::<TableOfContentsChapterEntry>()?);
+            } else if entry.starts_with(CHAPTER) {
+                sections.insert(entry.into_owned(), map.next_value::<ChapterEntry>()?);
+            } else if entry.starts_with(WORK) {
+                works.insert(entry.into_owned(), map.next_value::<WorkEntry>()?);
+            } else if entry.starts_with(USER) {
+                users.insert(entry.into_owned(), map.next_value::<UserAccountEntry>()?);
+            } else if entry == ROOT_QUERY {
+                let query = map.next_value::<HashMap<String, serde_json::Value>>()?;
+                root_work = query.into_iter().find(|(key, _)| key.starts_with(ROOT_WORK)).and_then(|(_, value)| match value {
+                    serde_json::Value::Object(mut value) => match value.remove("__ref") {
+                        Some(serde_json::Value::String(value)) => Some(value),
+                        _ => None,
+                    },
+                    _ => None,
+                });
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+
+        //Page's work is referenced by root query, but fallback to the only work, if present.
+        let work = match root_work.and_then(|key| works.remove(&key)) {
+            Some(work) => work,
+            None => {
+                let mut works = works.into_values();
+                match (works.next(), works.next()) {
+                    (Some(work), None) => work,
+                    _ => return Err(serde::de::Error::custom("Unable to find Work in __APOLLO_STATE__")),
+                }
+            }
+        };
+        let author = work.author.and_then(|author| users.remove(&author.__ref)).and_then(|author| author.activity_name.or(author.name));
+
+        //Reading order is defined by table of contents, which also filters out episodes of other works.
+        let mut chapters = Vec::new();
+        let mut toc = Vec::new();
+        for toc_chapter in work.table_of_contents.iter() {
+            let toc_chapter = match toc_chapters.get(&toc_chapter.__ref) {
+                Some(toc_chapter) => toc_chapter,
+                None => return Err(serde::de::Error::custom(format_args!("Missing table of contents entry '{}'", toc_chapter.__ref))),
+            };
+            let section = match toc_chapter.chapter.as_ref() {
+                Some(section) => match sections.remove(&section.__ref) {
+                    Some(section) => Some(section),
+                    None => return Err(serde::de::Error::custom(format_args!("Missing chapter '{}'", section.__ref))),
+                },
+                None => None,
+            };
+            let start = chapters.len();
+            for episode in toc_chapter.episode_unions.iter() {
+                chapters.push(match episodes.remove(&episode.__ref) {
+                    Some(episode) => Episode {
+                        id: episode.id,
+                        title: episode.title,
+                        published_at: episode.published_at,
+                        characters: episode.character_count,
+                    },
+                    None => match episode.__ref.strip_prefix(EPISODE) {
+                        Some(id) => Episode {
+                            id: id.to_owned(),
+                            title: None,
+                            published_at: None,
+                            characters: None,
+                        },
+                        None => return Err(serde::de::Error::custom(format_args!("Invalid episode reference '{}'", episode.__ref))),
+                    },
+                });
+            }
+
+            if let Some(section) = section {
+                toc.push(Section {
+                    title: section.title,
+                    level: section.level,
+                    chapters: start..chapters.len(),
+                });
             }
-            let _ = map.next_value::<IgnoredAny>();
         }
 
         Ok(ApolloState {
+            work: Work {
+                id: work.id,
+                title: work.title,
+                author,
+                catchphrase: work.catchphrase,
+                introduction: work.introduction,
+                genre: work.genre,
+                tags: work.tag_labels,
+                status: work.serial_status,
+                total_characters: work.total_character_count,
+                episode_count: work.public_episode_count,
+                published_at: work.published_at,
+                updated_at: work.last_episode_published_at,
+                stars: work.total_review_point,
+                followers: work.total_followers,
+                warnings: ContentWarnings {
+                    cruel: work.is_cruel,
+                    violent: work.is_violent,
+                    sexual: work.is_sexual,
+                },
+            },
+            sections: toc,
             chapters
         })
     }
@@ -116,10 +232,134 @@ impl<'de> serde::Deserialize<'de> for ApolloState {
     }
 }
 
-#[derive(Debug)]
-pub struct Index<'a> {
-    pub title: Option<&'a str>,
-    pub chapters: Vec<String>
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde_derive::Serialize, serde_derive::Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+///Serialization status of the work
+pub enum SerialStatus {
+    ///連載中
+    Running,
+    ///完結
+    Completed,
+    #[serde(other)]
+    ///Status not known to this tool
+    Unknown,
+}
+
+impl fmt::Display for SerialStatus {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Running => fmt.write_str("連載中"),
+            Self::Completed => fmt.write_str("完結"),
+            Self::Unknown => fmt.write_str("不明"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde_derive::Serialize)]
+///Content warnings declared by author
+pub struct ContentWarnings {
+    ///残酷描写有り
+    pub cruel: bool,
+    ///暴力描写有り
+    pub violent: bool,
+    ///性描写有り
+    pub sexual: bool,
+}
+
+#[derive(Clone, Debug, serde_derive::Serialize)]
+///Work metadata
+pub struct Work {
+    pub id: String,
+    pub title: String,
+    ///Author's activity name
+    pub author: Option<String>,
+    pub catchphrase: Option<String>,
+    pub introduction: Option<String>,
+    pub genre: Option<String>,
+    pub tags: Vec<String>,
+    pub status: Option<SerialStatus>,
+    ///Total number of characters in all public episodes
+    pub total_characters: Option<u64>,
+    ///Number of public episodes
+    pub episode_count: Option<u64>,
+    ///Date of first publication in ISO 8601 format
+    pub published_at: Option<String>,
+    ///Date of last episode publication in ISO 8601 format
+    pub updated_at: Option<String>,
+    ///Total number of review stars, if shown on the page
+    pub stars: Option<u64>,
+    ///Number of followers, if shown on the page
+    pub followers: Option<u64>,
+    pub warnings: ContentWarnings,
+}
+
+#[derive(Clone, Debug)]
+///Episode as listed in table of contents
+pub struct Episode {
+    pub id: String,
+    pub title: Option<String>,
+    ///Publication date in ISO 8601 format
+    pub published_at: Option<String>,
+    ///Number of characters, if listed in table of contents
+    pub characters: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+///Section (章) of the work
+pub struct Section {
+    pub title: String,
+    ///Nesting level, starting from 1
+    pub level: u8,
+    ///Range of episodes within [Index::chapters](Index::chapters) that directly belong to the section
+    pub chapters: Range<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Index {
+    pub work: Work,
+    ///Sections in reading order
+    pub sections: Vec<Section>,
+    ///Episodes in reading order
+    pub chapters: Vec<Episode>
+}
+
+impl Index {
+    ///Returns positions of sections to be written before chapter `idx`
+    ///
+    ///When `first` is set, returns all sections enclosing chapter `idx`, so that output that starts in the middle of the work still has its headings.
+    pub fn sections_before(&self, idx: usize, first: bool) -> Vec<usize> {
+        if !first {
+            return self.sections.iter().enumerate().filter(|(_, section)| section.chapters.start == idx).map(|(position, _)| position).collect();
+        }
+
+        let mut result: Vec<usize> = Vec::new();
+        for (position, section) in self.sections.iter().enumerate().take_while(|(_, section)| section.chapters.start <= idx) {
+            while let Some(last) = result.last() {
+                if self.sections[*last].level >= section.level {
+                    result.pop();
+                } else {
+                    break;
+                }
+            }
+            result.push(position);
+        }
+        result
+    }
+
+    ///Returns positions of sections to be written before each of `chapters`, which must be sorted
+    ///
+    ///Each section belongs to the first chapter, that starts it or, after a gap, is enclosed by it,
+    ///regardless of order in which chapters are written.
+    pub fn section_headings(&self, chapters: &[usize]) -> Vec<Vec<usize>> {
+        let mut assigned = vec![false; self.sections.len()];
+        let mut previous = None;
+        chapters.iter().map(|idx| {
+            let first = previous.map_or(true, |previous| previous + 1 != *idx);
+            previous = Some(*idx);
+            self.sections_before(*idx, first).into_iter().filter(|section| !core::mem::replace(&mut assigned[*section], true)).collect()
+        }).collect()
+    }
 }
 
 pub struct ChapterSelector {
@@ -138,6 +378,209 @@ impl ChapterSelector {
     }
 }
 
+///Writes text escaping HTML special characters, including quotes if `quotes` is set
+pub(crate) fn escape(out: &mut String, text: &str, quotes: bool) {
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if quotes => out.push_str("&quot;"),
+            '\'' if quotes => out.push_str("&apos;"),
+            ch => out.push(ch),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde_derive::Serialize, serde_derive::Deserialize)]
+#[serde(rename_all = "lowercase")]
+///Furigana (ruby) rendering mode
+pub enum Ruby {
+    ///Keeps `<ruby>` markup as it is.
+    Html,
+    ///Converts to `漢字（かんじ）`
+    Paren,
+    ///Converts to `｜漢字《かんじ》`
+    Aozora,
+    ///Removes readings, leaving only base text.
+    Strip,
+}
+
+impl Ruby {
+    ///Writes ruby annotation according to the mode, writing base text and reading via `escape`.
+    fn write_with(&self, out: &mut String, base: &str, reading: &str, escape: fn(&mut String, &str)) {
+        match self {
+            Self::Html => {
+                out.push_str("<ruby><rb>");
+                escape(out, base);
+                out.push_str("</rb><rp>（</rp><rt>");
+                escape(out, reading);
+                out.push_str("</rt><rp>）</rp></ruby>");
+            },
+            Self::Paren => {
+                escape(out, base);
+                out.push('（');
+                escape(out, reading);
+                out.push('）');
+            },
+            Self::Aozora => {
+                out.push('｜');
+                escape(out, base);
+                out.push('《');
+                escape(out, reading);
+                out.push('》');
+            },
+            Self::Strip => escape(out, base),
+        }
+    }
+}
+
+impl Default for Ruby {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::Html
+    }
+}
+
+impl FromStr for Ruby {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if text.eq_ignore_ascii_case("html") {
+            Ok(Self::Html)
+        } else if text.eq_ignore_ascii_case("paren") {
+            Ok(Self::Paren)
+        } else if text.eq_ignore_ascii_case("aozora") {
+            Ok(Self::Aozora)
+        } else if text.eq_ignore_ascii_case("strip") {
+            Ok(Self::Strip)
+        } else {
+            Err(())
+        }
+    }
+}
+
+///Returns base text and reading of `<ruby>` element
+fn ruby_text(ruby: scraper::ElementRef<'_>) -> (String, String) {
+    let mut base = String::new();
+    let mut reading = String::new();
+    for node in ruby.children() {
+        match node.value() {
+            scraper::Node::Text(text) => base.push_str(text),
+            scraper::Node::Element(element) => match element.name() {
+                "rp" => (),
+                "rt" => if let Some(rt) = scraper::ElementRef::wrap(node) {
+                    reading.extend(rt.text());
+                },
+                _ => if let Some(rb) = scraper::ElementRef::wrap(node) {
+                    base.extend(rb.text());
+                },
+            },
+            _ => (),
+        }
+    }
+
+    (base, reading)
+}
+
+fn write_ruby(out: &mut String, ruby: scraper::ElementRef<'_>, mode: Ruby, escape: fn(&mut String, &str)) {
+    let (base, reading) = ruby_text(ruby);
+    mode.write_with(out, &base, &reading, escape);
+}
+
+fn write_text_with(out: &mut String, element: scraper::ElementRef<'_>, ruby: Ruby, escape: fn(&mut String, &str)) {
+    for node in element.children() {
+        match node.value() {
+            scraper::Node::Text(text) => escape(out, text),
+            scraper::Node::Element(_) => if let Some(child) = scraper::ElementRef::wrap(node) {
+                if child.value().name() == "ruby" {
+                    write_ruby(out, child, ruby, escape);
+                } else {
+                    write_text_with(out, child, ruby, escape);
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+///Writes text content of the element, converting ruby annotations according to `ruby` mode.
+///
+///Text is escaped, while any markup other than ruby is discarded.
+#[inline(always)]
+pub fn write_text(out: &mut String, element: scraper::ElementRef<'_>, ruby: Ruby) {
+    write_text_with(out, element, ruby, |out, text| escape(out, text, false))
+}
+
+///Writes text content of the element without escaping, converting ruby annotations according to `ruby` mode.
+///
+///Any markup other than ruby is discarded. `Ruby::Html` writes ruby markup as it is.
+#[inline(always)]
+pub fn write_plain_text(out: &mut String, element: scraper::ElementRef<'_>, ruby: Ruby) {
+    write_text_with(out, element, ruby, String::push_str)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde_derive::Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+///Fragment of paragraph's text
+pub enum Span {
+    ///Plain text
+    Text {
+        text: String,
+    },
+    ///Text with furigana
+    Ruby {
+        base: String,
+        reading: String,
+    },
+    ///Text with emphasis dots (傍点)
+    Emphasis {
+        text: String,
+    },
+}
+
+fn push_span_text(spans: &mut Vec<Span>, text: &str) {
+    match spans.last_mut() {
+        Some(Span::Text { text: last }) => last.push_str(text),
+        _ => spans.push(Span::Text {
+            text: text.to_owned(),
+        }),
+    }
+}
+
+fn collect_spans(spans: &mut Vec<Span>, element: scraper::ElementRef<'_>) {
+    for node in element.children() {
+        match node.value() {
+            scraper::Node::Text(text) => push_span_text(spans, text),
+            scraper::Node::Element(_) => if let Some(child) = scraper::ElementRef::wrap(node) {
+                match child.value().name() {
+                    "ruby" => {
+                        let (base, reading) = ruby_text(child);
+                        spans.push(Span::Ruby {
+                            base,
+                            reading,
+                        });
+                    },
+                    "em" => spans.push(Span::Emphasis {
+                        text: child.text().collect(),
+                    }),
+                    _ => collect_spans(spans, child),
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+///Splits paragraph's text into fragments, discarding any markup other than ruby and emphasis.
+///
+///Text is not escaped.
+pub fn spans(element: scraper::ElementRef<'_>) -> Vec<Span> {
+    let mut spans = Vec::new();
+    collect_spans(&mut spans, element);
+    spans
+}
+
 pub enum Line<'a> {
     Paragraph(scraper::ElementRef<'a>),
     Break,
@@ -164,21 +607,20 @@ impl Document {
         }
     }
 
-    pub fn get_index(&self) -> Option<Result<Index<'_>, serde_json::Error>> {
-        let title = Selector::parse("title").unwrap();
-        let title = self.inner.select(&title).next().and_then(|title| {
-            title.text().next()
-        });
-
+    pub fn get_index(&self) -> Option<Result<Index, serde_json::Error>> {
         let selector = Selector::parse("script").unwrap();
         for elem in self.inner.select(&selector) {
             let element = elem.value();
             match (element.attr("type"), element.attr("id")) {
                 (Some("application/json"), Some("__NEXT_DATA__")) => {
                     if let Some(json) = elem.text().next() {
-                        return Some(serde_json::from_str::<ScriptState>(json).map(|result| Index {
-                            title,
-                            chapters: result.props.pageProps.__APOLLO_STATE__.chapters
+                        return Some(serde_json::from_str::<ScriptState>(json).map(|result| {
+                            let state = result.props.pageProps.__APOLLO_STATE__;
+                            Index {
+                                work: state.work,
+                                sections: state.sections,
+                                chapters: state.chapters,
+                            }
                         }));
                     } else {
                         continue;
@@ -190,13 +632,12 @@ impl Document {
         None
     }
 
-    pub fn get_chapter_content<'a>(&'a self, selectors: &'a ChapterSelector) -> Option<(Option<String>, impl Iterator<Item = Line> + 'a)> {
-        let title = self.inner.select(&selectors.title).next().map(|html| html.inner_html());
+    pub fn get_chapter_content<'a>(&'a self, selectors: &'a ChapterSelector) -> Option<(Option<String>, impl Iterator<Item = Line<'a>> + 'a)> {
+        let title = self.inner.select(&selectors.title).next().map(|html| html.text().collect());
+        self.get_chapter_lines(selectors).map(|lines| (title, lines))
+    }
 
-        if let Some(body) = self.inner.select(&selectors.body).next() {
-            Some((title, body.select(&selectors.line).map(Line::new)))
-        } else {
-            None
-        }
+    pub fn get_chapter_lines<'a>(&'a self, selectors: &'a ChapterSelector) -> Option<impl Iterator<Item = Line<'a>> + 'a> {
+        self.inner.select(&selectors.body).next().map(|body| body.select(&selectors.line).map(Line::new))
     }
 }
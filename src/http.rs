@@ -1,14 +1,27 @@
 #![allow(clippy::result_large_err)]
-use std::io;
+use std::{io, thread};
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::BTreeMap;
+use std::collections::hash_map::RandomState;
+use core::hash::{BuildHasher, Hasher};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use core::{time, fmt};
+use core::num::{NonZeroU32, NonZeroUsize};
+
+use crate::date;
 
 const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+///Limit of the response body size
+const MAX_BODY_SIZE: u64 = 10 * 1024 * 1024;
 
 #[derive(Debug)]
 pub enum Error {
     StatusFailed(u16),
     Transport(ureq::Transport),
-    Read(io::Error)
+    Read(io::Error),
+    ///Recorded session cannot be accessed
+    Session(io::Error),
 }
 
 impl fmt::Display for Error {
@@ -18,6 +31,31 @@ impl fmt::Display for Error {
             Self::StatusFailed(code) => fmt.write_fmt(format_args!("Request failed with status={code}")),
             Self::Transport(reason) => fmt.write_fmt(format_args!("Unable to connect: {reason}")),
             Self::Read(reason) => fmt.write_fmt(format_args!("Unable to read response: {reason}")),
+            Self::Session(reason) => fmt.write_fmt(format_args!("Unable to access recorded session: {reason}")),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    #[inline]
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::StatusFailed(_) => None,
+            Self::Transport(error) => Some(error),
+            Self::Read(error) => Some(error),
+            Self::Session(error) => Some(error),
+        }
+    }
+}
+
+impl Error {
+    ///Returns whether request can be retried after this error
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::StatusFailed(code) => matches!(code, 408 | 425 | 429 | 500 | 502 | 503 | 504),
+            Self::Transport(error) => !matches!(error.kind(), ureq::ErrorKind::InvalidUrl | ureq::ErrorKind::UnknownScheme | ureq::ErrorKind::InvalidProxyUrl | ureq::ErrorKind::ProxyUnauthorized | ureq::ErrorKind::TooManyRedirects | ureq::ErrorKind::BadHeader),
+            Self::Read(_) => true,
+            Self::Session(_) => false,
         }
     }
 }
@@ -39,44 +77,346 @@ impl From<io::Error> for Error {
     }
 }
 
+///Response to HTTP request
+pub struct Response {
+    pub status: u16,
+    ///Headers with lowercase names
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    ///Returns value of the first header with specified lowercase `name`
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(header, _)| header == name).map(|(_, value)| value.as_str())
+    }
+}
+
+///Means to perform HTTP requests, allowing to replace network in tests
+pub trait Transport: Send + Sync {
+    ///Performs GET request, returning response with any status
+    ///
+    ///Failure to connect should be reported as `Error::Transport` or `Error::Read`.
+    fn get(&self, url: &str, timeout: time::Duration) -> Result<Response, Error>;
+}
+
+///Transport over network using `ureq`
+pub struct Ureq {
+    inner: ureq::Agent,
+}
+
+impl Ureq {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            inner: ureq::builder().try_proxy_from_env(true).redirects(5).user_agent(USER_AGENT).build(),
+        }
+    }
+}
+
+impl Transport for Ureq {
+    fn get(&self, url: &str, timeout: time::Duration) -> Result<Response, Error> {
+        let response = match self.inner.get(url).timeout(timeout).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(ureq::Error::Transport(error)) => return Err(Error::Transport(error)),
+        };
+
+        let status = response.status();
+        let headers = response.headers_names().into_iter().filter_map(|name| {
+            let value = response.header(&name)?.to_owned();
+            Some((name.to_ascii_lowercase(), value))
+        }).collect();
+        let mut body = Vec::new();
+        io::Read::read_to_end(&mut io::Read::take(response.into_reader(), MAX_BODY_SIZE + 1), &mut body)?;
+        if body.len() as u64 > MAX_BODY_SIZE {
+            return Err(Error::Read(io::Error::new(io::ErrorKind::InvalidData, "response is too big")));
+        }
+
+        Ok(Response {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
 pub trait FromResponse: Sized {
-    fn read_response(resp: ureq::Response) -> Result<Self, Error>;
+    fn read_response(resp: Response) -> Result<Self, Error>;
 }
 
 impl FromResponse for () {
     #[inline(always)]
-    fn read_response(_: ureq::Response) -> Result<Self, Error> {
+    fn read_response(_: Response) -> Result<Self, Error> {
         Ok(())
     }
 }
 
 impl FromResponse for String {
     #[inline(always)]
-    fn read_response(resp: ureq::Response) -> Result<Self, Error> {
-        resp.into_string().map_err(Into::into)
+    fn read_response(resp: Response) -> Result<Self, Error> {
+        String::from_utf8(resp.body).map_err(|error| Error::Read(io::Error::new(io::ErrorKind::InvalidData, error)))
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+///Policy to retry failed requests, using exponential backoff with jitter
+pub struct Retry {
+    ///Number of retries after first failed attempt
+    pub retries: u32,
+    ///Delay before first retry
+    pub delay: time::Duration,
+    ///Maximum delay between attempts, unless server requests more via `Retry-After`
+    pub max_delay: time::Duration,
+    ///Longest delay requested via `Retry-After`, that is honored. Request fails, if server asks to wait longer.
+    pub max_retry_after: time::Duration,
+}
+
+impl Retry {
+    ///Policy that never retries
+    pub const fn none() -> Self {
+        Self {
+            retries: 0,
+            delay: time::Duration::from_secs(0),
+            max_delay: time::Duration::from_secs(0),
+            max_retry_after: time::Duration::from_secs(0),
+        }
+    }
+
+    ///Returns delay before retry number `attempt`, starting from 0
+    fn delay(&self, attempt: u32) -> time::Duration {
+        let delay = self.delay.saturating_mul(1u32.checked_shl(attempt).unwrap_or(u32::MAX)).min(self.max_delay);
+        //Jitter within [delay / 2, delay]
+        let jitter = RandomState::new().build_hasher().finish() % 1024;
+        delay / 2 + (delay / 2).mul_f64(jitter as f64 / 1023.0)
+    }
+}
+
+impl Default for Retry {
+    #[inline(always)]
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            delay: time::Duration::from_secs(1),
+            max_delay: time::Duration::from_secs(30),
+            max_retry_after: time::Duration::from_secs(120),
+        }
+    }
+}
+
+///Parses HTTP-date in preferred format (e.g. `Sun, 06 Nov 1994 08:49:37 GMT`) as seconds since UNIX epoch
+///
+///Obsolete formats are not accepted.
+fn parse_http_date(value: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let mut parts = value.split_ascii_whitespace();
+    let (_, day, month, year, time, zone) = (parts.next()?.strip_suffix(',')?, parts.next()?, parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+    if zone != "GMT" || parts.next().is_some() || day.len() != 2 || year.len() != 4 {
+        return None;
+    }
+    let day: i64 = day.parse().ok()?;
+    let month = MONTHS.iter().position(|name| *name == month)? as i64 + 1;
+    let year: i64 = year.parse().ok()?;
+    let mut time = time.split(':').map(|part| if part.len() == 2 { part.parse::<i64>().ok() } else { None });
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    if time.next().is_some() || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let seconds = date::days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    if seconds < 0 {
+        None
+    } else {
+        Some(seconds as u64)
+    }
+}
+
+///Parses value of `Retry-After` as either number of seconds or HTTP-date relative to `now`
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<time::Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(time::Duration::from_secs(seconds));
+    }
+
+    let at = UNIX_EPOCH + time::Duration::from_secs(parse_http_date(value)?);
+    //Date in the past means no wait
+    Some(at.duration_since(now).unwrap_or(time::Duration::ZERO))
+}
+
+struct RateLimit {
+    interval: time::Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimit {
+    ///Blocks until next request is allowed
+    fn wait(&self) {
+        let now = Instant::now();
+        let at = {
+            let mut next = match self.next.lock() {
+                Ok(next) => next,
+                Err(error) => error.into_inner(),
+            };
+            let at = (*next).max(now);
+            *next = at + self.interval;
+            at
+        };
+
+        if at > now {
+            thread::sleep(at - now);
+        }
     }
 }
 
 pub struct Client {
-    inner: ureq::Agent,
+    transport: Box<dyn Transport>,
     timeout: time::Duration,
+    retry: Retry,
+    rate_limit: Option<RateLimit>,
 }
 
 impl Client {
     #[inline]
     pub fn new() -> Self {
         Self {
-            inner: ureq::builder().try_proxy_from_env(true).redirects(5).user_agent(USER_AGENT).build(),
+            transport: Box::new(Ureq::new()),
             timeout: time::Duration::from_secs(5),
+            retry: Retry::default(),
+            rate_limit: None,
         }
     }
 
+    ///Sets transport to perform requests, `Ureq` by default
+    pub fn with_transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Box::new(transport);
+        self
+    }
+
+    ///Sets policy to retry failed requests
+    pub fn with_retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    ///Limits number of requests per second, shared by all users of the client
+    pub fn with_rate_limit(mut self, requests_per_second: NonZeroU32) -> Self {
+        self.rate_limit = Some(RateLimit {
+            interval: time::Duration::from_secs(1) / requests_per_second.get(),
+            next: Mutex::new(Instant::now()),
+        });
+        self
+    }
+
+    ///Performs GET request, retrying according to retry policy
     pub fn get<T: FromResponse>(&self, url: &str) -> Result<T, Error> {
-        let response = self.inner.get(url).timeout(self.timeout).call()?;
-        if response.status() != 200 {
-            Err(Error::StatusFailed(response.status()))
-        } else {
-            T::read_response(response)
+        let mut attempt = 0;
+        loop {
+            let (error, retry_after) = match self.get_once(url) {
+                Ok(result) => return Ok(result),
+                Err(error) => error,
+            };
+
+            if attempt >= self.retry.retries || !error.is_retryable() {
+                return Err(error);
+            }
+
+            let delay = self.retry.delay(attempt);
+            thread::sleep(match retry_after {
+                Some(retry_after) if retry_after > self.retry.max_retry_after => return Err(error),
+                Some(retry_after) => retry_after.max(delay),
+                None => delay,
+            });
+            attempt += 1;
+        }
+    }
+
+    fn get_once<T: FromResponse>(&self, url: &str) -> Result<T, (Error, Option<time::Duration>)> {
+        if let Some(rate_limit) = self.rate_limit.as_ref() {
+            rate_limit.wait();
+        }
+
+        let response = self.transport.get(url, self.timeout).map_err(|error| (error, None))?;
+        match response.status {
+            200 => T::read_response(response).map_err(|error| (error, None)),
+            code => {
+                //Server asks to wait, when overloaded
+                let retry_after = match code {
+                    429 | 503 => response.header("retry-after").and_then(|value| parse_retry_after(value, SystemTime::now())),
+                    _ => None,
+                };
+                Err((Error::StatusFailed(code), retry_after))
+            }
         }
     }
 }
+
+///Downloads multiple urls concurrently, yielding responses in the order of urls
+pub struct Downloads<T> {
+    receiver: mpsc::Receiver<(usize, Result<T, Error>)>,
+    pending: BTreeMap<usize, Result<T, Error>>,
+    next: usize,
+    len: usize,
+}
+
+impl<T: FromResponse + Send + 'static> Downloads<T> {
+    ///Starts downloading `urls` using up to `jobs` threads
+    ///
+    ///Workers stop once `Downloads` is dropped.
+    pub fn new(client: Arc<Client>, urls: Vec<String>, jobs: NonZeroUsize) -> Self {
+        let len = urls.len();
+        let (sender, receiver) = mpsc::channel();
+        let urls = Arc::new(urls);
+        let cursor = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..jobs.get().min(len) {
+            let client = client.clone();
+            let urls = urls.clone();
+            let cursor = cursor.clone();
+            let sender = sender.clone();
+            thread::spawn(move || loop {
+                let idx = cursor.fetch_add(1, Ordering::Relaxed);
+                let url = match urls.get(idx) {
+                    Some(url) => url,
+                    None => break,
+                };
+
+                if sender.send((idx, client.get(url))).is_err() {
+                    break;
+                }
+            });
+        }
+
+        Self {
+            receiver,
+            pending: BTreeMap::new(),
+            next: 0,
+            len,
+        }
+    }
+}
+
+impl<T> Iterator for Downloads<T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.len {
+            return None;
+        }
+
+        let result = loop {
+            if let Some(result) = self.pending.remove(&self.next) {
+                break result;
+            }
+
+            match self.receiver.recv() {
+                Ok((idx, result)) => {
+                    self.pending.insert(idx, result);
+                },
+                Err(_) => break Err(Error::Read(io::Error::new(io::ErrorKind::Other, "Download workers stopped unexpectedly"))),
+            }
+        };
+        self.next += 1;
+        Some(result)
+    }
+}
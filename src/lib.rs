@@ -3,3 +3,10 @@ pub mod cli;
 pub mod http;
 pub mod stdio;
 pub mod html;
+pub mod render;
+pub mod state;
+pub mod select;
+pub mod novel;
+pub mod error;
+pub mod session;
+mod date;
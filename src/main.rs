@@ -1,8 +1,20 @@
 use kakuyomu_dump::*;
 
-use std::{fs, path};
+use std::{io, fs, path};
 use std::process::ExitCode;
-use core::num::NonZeroUsize;
+use std::sync::Arc;
+use core::num::NonZeroU32;
+
+use unicode_width::UnicodeWidthStr;
+
+///Exit code, when some chapters could not be downloaded and `--strict` is set
+const EXIT_INCOMPLETE: u8 = 3;
+///Exit code, when request failed, which is likely to be temporary
+const EXIT_NETWORK: u8 = 4;
+///Exit code, when work does not exist (e.g. it is deleted)
+const EXIT_NOT_FOUND: u8 = 5;
+///Exit code, when page lacks expected content or cannot be parsed, likely due to change of the site's layout
+const EXIT_LAYOUT_CHANGED: u8 = 6;
 
 fn args_from_stdin(stdio: &stdio::Io) -> Result<cli::Cli, ExitCode> {
     let mut stdin = stdio.stdin();
@@ -28,85 +40,123 @@ fn args_from_stdin(stdio: &stdio::Io) -> Result<cli::Cli, ExitCode> {
 
     let novel;
     loop {
-        prompt!(">Please input novel id (e.g. 1177354054883819762): ");
+        prompt!(">Please input novel id or URL (e.g. 1177354054883819762): ");
         let line = read_line!();
         if line.is_empty() {
             continue;
         }
 
-        novel = line.to_owned();
-        break;
+        match line.parse() {
+            Ok(value) => {
+                novel = value;
+                break;
+            },
+            Err(_) => {
+                stderr.write_fmtn(format_args!("!>>>'{line}': Not a kakuyomu novel id or URL"));
+                continue;
+            }
+        }
     }
 
-    let from;
-    prompt!(">Please specify which chapters to download:\n");
+    let select;
     loop {
-        prompt!("Start FROM chapter(defaults to 1)?:");
+        prompt!(">Please specify which chapters to download, e.g. 1-10,15,40-,last:5,id:<episode id>,section:<title> (leave empty for all): ");
         let line = read_line!();
         if line.is_empty() {
-            from = cli::default_from_value();
+            select = None;
             break;
         }
 
-        match usize::from_str_radix(line, 10) {
-            Ok(chapter) => match NonZeroUsize::new(chapter) {
-                Some(chapter) => {
-                    from = chapter;
-                    break;
-                },
-                None => {
-                    stderr.write_fmtn(format_args!("!>>>Chapter cannot be zero"));
-                    continue
-                }
+        match line.parse() {
+            Ok(value) => {
+                select = Some(value);
+                break;
             },
-            Err(error) => {
-                stderr.write_fmtn(format_args!("!>>>'{line}': {error}"));
+            Err(_) => {
+                stderr.write_fmtn(format_args!("!>>>'{line}': Invalid chapter selection"));
                 continue;
             }
         }
     }
 
-    let to;
+    prompt!(">Specify output file (leave empty for default): ");
+    let line = read_line!();
+    let out = if line.is_empty() {
+        None
+    } else {
+        Some(line.to_owned())
+    };
+
+    let format;
     loop {
-        prompt!("TO chapter(leave empty for all)?:");
+        prompt!(">Specify output format: md, epub, txt, aozora, json, ndjson or html (leave empty for md): ");
         let line = read_line!();
         if line.is_empty() {
-            to = None;
+            format = render::Format::Markdown;
             break;
         }
 
-        match usize::from_str_radix(line, 10) {
-            Ok(chapter) => if chapter > from.get() {
-                to = Some(unsafe {
-                    NonZeroUsize::new_unchecked(chapter)
-                });
+        match line.parse() {
+            Ok(value) => {
+                format = value;
                 break;
-            } else {
-                stderr.write_fmtn(format_args!("!>>>Number has to be greater than from='{from}'"));
-                continue
             },
-            Err(error) => {
-                stderr.write_fmtn(format_args!("!>>>{error}"));
+            Err(_) => {
+                stderr.write_fmtn(format_args!("!>>>'{line}': Unknown format"));
                 continue;
             }
         }
     }
 
-    prompt!(">Specify output file (leave empty for default): ");
-    let line = read_line!();
-    let out = if line.is_empty() {
-        None
-    } else {
-        Some(line.to_owned())
-    };
+    let ruby;
+    loop {
+        prompt!(">Specify furigana rendering: html, paren, aozora or strip (leave empty for html): ");
+        let line = read_line!();
+        if line.is_empty() {
+            ruby = html::Ruby::Html;
+            break;
+        }
+
+        match line.parse() {
+            Ok(value) => {
+                ruby = value;
+                break;
+            },
+            Err(_) => {
+                stderr.write_fmtn(format_args!("!>>>'{line}': Unknown furigana mode"));
+                continue;
+            }
+        }
+    }
 
     stdout.write_newline();
 
     Ok(cli::Cli {
-        from,
-        to,
+        from: None,
+        to: None,
+        select,
+        since: None,
+        until: None,
         out,
-        novel
+        format,
+        ruby,
+        wrap: None,
+        sjis: false,
+        vertical: false,
+        split: false,
+        resume: false,
+        update: None,
+        info: false,
+        json: false,
+        dry_run: false,
+        jobs: cli::default_jobs_value(),
+        rate: 5,
+        retries: 3,
+        strict: false,
+        record: None,
+        replay: None,
+        batch: None,
+        novels: vec![novel],
     })
 }
 
@@ -123,159 +173,606 @@ fn main() -> ExitCode {
     }
 }
 
-fn construct_file_path(dir: &str, name: &str) -> path::PathBuf {
+///Prints work metadata in human readable form
+fn print_info(stdout: &mut stdio::Out<impl io::Write, stdio::behavior::Ignore>, url: &str, index: &html::Index) {
+    let work = &index.work;
+    stdout.write_fmtn(format_args!("Title: {}", work.title));
+    stdout.write_fmtn(format_args!("URL: {url}"));
+    let fields = [
+        ("Author", work.author.clone()),
+        ("Catchphrase", work.catchphrase.clone()),
+        ("Genre", work.genre.clone()),
+        ("Tags", Some(work.tags.join(", ")).filter(|tags| !tags.is_empty())),
+        ("Status", work.status.map(|status| status.to_string())),
+        ("Episodes", Some(work.episode_count.unwrap_or(index.chapters.len() as u64).to_string())),
+        ("Sections", Some(index.sections.len().to_string()).filter(|_| !index.sections.is_empty())),
+        ("Characters", work.total_characters.map(|characters| characters.to_string())),
+        ("Published", work.published_at.clone()),
+        ("Updated", work.updated_at.clone()),
+        ("Stars", work.stars.map(|stars| stars.to_string())),
+        ("Followers", work.followers.map(|followers| followers.to_string())),
+    ];
+    for (name, value) in fields.iter() {
+        if let Some(value) = value {
+            stdout.write_fmtn(format_args!("{name}: {value}"));
+        }
+    }
+
+    let mut warnings = Vec::new();
+    if work.warnings.cruel {
+        warnings.push("残酷描写有り");
+    }
+    if work.warnings.violent {
+        warnings.push("暴力描写有り");
+    }
+    if work.warnings.sexual {
+        warnings.push("性描写有り");
+    }
+    if !warnings.is_empty() {
+        stdout.write_fmtn(format_args!("Warnings: {}", warnings.join(", ")));
+    }
+    if let Some(introduction) = work.introduction.as_deref() {
+        stdout.write_fmtn(format_args!("Introduction:\n{introduction}"));
+    }
+}
+
+///Formats chapter positions as list of chapter numbers and their ranges, e.g. `1-10,15`
+fn format_ranges(chapters: &[usize]) -> String {
+    let mut result = String::new();
+    let mut idx = 0;
+    while idx < chapters.len() {
+        let start = chapters[idx];
+        let mut end = start;
+        while chapters.get(idx + 1) == Some(&(end + 1)) {
+            end += 1;
+            idx += 1;
+        }
+        if !result.is_empty() {
+            result.push(',');
+        }
+        if start == end {
+            result.push_str(&format!("{}", start + 1));
+        } else {
+            result.push_str(&format!("{}-{}", start + 1, end + 1));
+        }
+        idx += 1;
+    }
+    result
+}
+
+fn construct_file_path(dir: &str, name: &str, format: render::Format) -> path::PathBuf {
     let mut path = path::PathBuf::from(dir);
     path.push(name);
-    path.set_extension("md");
+    path.set_extension(format.extension());
 
     path
 }
 
-fn run(io: stdio::Io, args: cli::Cli) -> ExitCode {
-    let http = http::Client::new();
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+///Result of novel dump, ordered by severity
+enum Outcome {
+    Success,
+    ///Some chapters could not be downloaded
+    Incomplete,
+    ///Request failed
+    Network,
+    ///Work does not exist
+    NotFound,
+    ///Site's layout is not recognized
+    LayoutChanged,
+    Failure,
+}
+
+impl Outcome {
+    fn exit_code(self, strict: bool) -> ExitCode {
+        match self {
+            Self::Success => ExitCode::SUCCESS,
+            Self::Incomplete if strict => ExitCode::from(EXIT_INCOMPLETE),
+            Self::Incomplete => ExitCode::SUCCESS,
+            Self::Network => ExitCode::from(EXIT_NETWORK),
+            Self::NotFound => ExitCode::from(EXIT_NOT_FOUND),
+            Self::LayoutChanged => ExitCode::from(EXIT_LAYOUT_CHANGED),
+            Self::Failure => ExitCode::FAILURE,
+        }
+    }
+}
+
+impl From<&error::Error> for Outcome {
+    #[inline]
+    fn from(error: &error::Error) -> Self {
+        match error {
+            error::Error::NotFound { .. } => Self::NotFound,
+            error::Error::Network { .. } => Self::Network,
+            error::Error::LayoutChanged { .. } | error::Error::Parse { .. } => Self::LayoutChanged,
+            error::Error::Session { .. } | error::Error::Output { .. } => Self::Failure,
+        }
+    }
+}
+
+///Reads novels from batch list, one per line, skipping empty lines and `#` comments
+fn read_batch(path: &str) -> io::Result<Vec<(usize, String)>> {
+    let list = if path == "-" {
+        let mut list = String::new();
+        io::Read::read_to_string(&mut io::stdin(), &mut list)?;
+        list
+    } else {
+        fs::read_to_string(path)?
+    };
+
+    Ok(list.lines().enumerate().map(|(idx, line)| (idx + 1, line.trim())).filter(|(_, line)| !line.is_empty() && !line.starts_with('#')).map(|(idx, line)| (idx, line.to_owned())).collect())
+}
+
+fn run(io: stdio::Io, mut args: cli::Cli) -> ExitCode {
     let mut stderr = io.stderr().ignore_errors();
     let mut stdout = io.stdout().ignore_errors();
 
-    let novel_url = format!("https://kakuyomu.jp/works/{}", args.novel);
-    let body: String = loop {
-        stdout.write_fmt(format_args!(">>>{novel_url}: Fetch novel index..."));
-        match http.get(&novel_url) {
-            Ok(body) => {
-                stdout.write_fmtn(format_args!("OK"));
-                break body;
-            }
-            Err(http::Error::StatusFailed(404)) => {
-                stdout.write_fmtn(format_args!("ERR"));
-                stderr.write_fmtn(format_args!("No such novel found"));
+    let mut novels = args.novels.clone();
+    if let Some(batch) = args.batch.as_ref() {
+        match read_batch(batch) {
+            Ok(list) => for (line, novel) in list {
+                match novel.parse() {
+                    Ok(novel) => novels.push(novel),
+                    Err(_) => {
+                        stderr.write_fmtn(format_args!("{batch}:{line}: '{novel}' is not a kakuyomu novel id or URL"));
+                        return ExitCode::FAILURE
+                    }
+                }
+            },
+            Err(error) => {
+                stderr.write_fmtn(format_args!("{batch}: Cannot read: {error}"));
                 return ExitCode::FAILURE
             }
+        }
+    }
+
+    //Update takes novel and output options from the state of existing output
+    if let Some(update) = args.update.clone() {
+        if !novels.is_empty() || args.out.is_some() {
+            stderr.write_fmtn(format_args!("Option --update cannot be used with novels or --out"));
+            return ExitCode::FAILURE
+        }
+
+        let out = update.strip_suffix(state::EXTENSION).unwrap_or(&update).to_owned();
+        let state_path = state::State::path(path::Path::new(&out));
+        let state = match state::State::load(&state_path) {
+            Ok(state) => state,
             Err(error) => {
-                stdout.write_fmtn(format_args!("ERR"));
-                stderr.write_fmtn(format_args!("{error}"));
-                continue
+                stderr.write_fmtn(format_args!("{}: Cannot update: {error}", state_path.display()));
+                return ExitCode::FAILURE
             }
         };
-    };
+        novels.push(cli::Novel {
+            id: state.novel,
+            episode: None,
+        });
+        args.out = Some(out);
+        args.format = state.format;
+        args.ruby = state.ruby;
+        args.sjis = state.sjis;
+        args.wrap = state.wrap;
+    }
 
-    let index = html::Document::new(&body);
-    let index = match index.get_index() {
-        Some(Ok(index)) => index,
-        Some(Err(error)) => {
-            stderr.write_fmtn(format_args!("Unable to deserialize chapter index: {error}"));
-            return ExitCode::FAILURE
-        }
-        None => {
-            stderr.write_fmtn(format_args!("Unable to fetch chapter index"));
-            return ExitCode::FAILURE
+    //Same work listed twice would only overwrite its own output
+    let mut idx = 0;
+    while idx < novels.len() {
+        if novels[..idx].iter().any(|novel| novel.id == novels[idx].id) {
+            novels.remove(idx);
+        } else {
+            idx += 1;
         }
-    };
+    }
 
-    let mut url = String::new();
-    let max = match args.to {
-        Some(max) => if max.get() > index.chapters.len() {
-            stderr.write_fmtn(format_args!("Novel has only {} chapters, but option -to is set to '{}'", index.chapters.len(), max));
+    if novels.is_empty() {
+        stderr.write_fmtn(format_args!("No novel is specified"));
+        return ExitCode::FAILURE
+    } else if args.select.is_some() && (args.from.is_some() || args.to.is_some()) {
+        stderr.write_fmtn(format_args!("Option --select cannot be used with --from or --to"));
+        return ExitCode::FAILURE
+    } else if novels.len() > 1 && args.out.is_some() {
+        stderr.write_fmtn(format_args!("Option --out cannot be used with multiple novels"));
+        return ExitCode::FAILURE
+    } else if args.json && !args.info {
+        stderr.write_fmtn(format_args!("Option --json can only be used with --info"));
+        return ExitCode::FAILURE
+    }
+
+    let mut http = http::Client::new().with_retry(http::Retry {
+        retries: args.retries,
+        ..Default::default()
+    });
+    match (args.record.as_ref(), args.replay.as_ref()) {
+        (Some(_), Some(_)) => {
+            stderr.write_fmtn(format_args!("Options --record and --replay cannot be used together"));
             return ExitCode::FAILURE
-        } else {
-            max.get()
         },
-        None => index.chapters.len()
-    };
-
-    let title = match index.title {
-        Some(title) => {
-            let title = html::Title::new(title);
-            stdout.write_fmtn(format_args!("Title: {}", title.name));
-            if let Some(author) = title.author {
-                stdout.write_fmtn(format_args!("Author: {}", author));
+        (Some(record), None) => match session::Record::new(http::Ureq::new(), record) {
+            Ok(record) => http = http.with_transport(record),
+            Err(error) => {
+                stderr.write_fmtn(format_args!("{record}: Cannot write: {error}"));
+                return ExitCode::FAILURE
             }
-            title.name
         },
-        None => {
-            stderr.write_fmtn(format_args!("Unable to recognize novel's title"));
-            return ExitCode::FAILURE
+        (None, Some(replay)) => http = http.with_transport(session::Replay::new(replay)),
+        (None, None) => (),
+    }
+    //Replay needs no limit
+    if let (Some(rate), None) = (NonZeroU32::new(args.rate), args.replay.as_ref()) {
+        http = http.with_rate_limit(rate);
+    }
+    let http = Arc::new(http);
+
+    if novels.len() == 1 {
+        return dump(&io, &http, &args, &novels[0]).exit_code(args.strict);
+    }
+
+    let mut results = Vec::with_capacity(novels.len());
+    for novel in novels.iter() {
+        if !args.is_quiet() {
+            stdout.write_fmtn(format_args!("==================="));
+        }
+        results.push(dump(&io, &http, &args, novel));
+    }
+
+    //Failures are already reported on stderr
+    if !args.is_quiet() {
+        stdout.write_fmtn(format_args!("==================="));
+        stdout.write_fmtn(format_args!("Summary:"));
+        for (novel, outcome) in novels.iter().zip(results.iter()) {
+            let status = match outcome {
+                Outcome::Success => "OK",
+                Outcome::Incomplete => "INCOMPLETE",
+                Outcome::Network => "NETWORK",
+                Outcome::NotFound => "NOT FOUND",
+                Outcome::LayoutChanged => "LAYOUT",
+                Outcome::Failure => "FAILED",
+            };
+            stdout.write_fmtn(format_args!("{status:<11} https://kakuyomu.jp/works/{}", novel.id));
+        }
+    }
+
+    match results.into_iter().max() {
+        Some(outcome) => outcome.exit_code(args.strict),
+        None => ExitCode::SUCCESS,
+    }
+}
+
+///Prints progress of the dump, recording written chapters within state
+struct Report<'a, O: io::Write, E: io::Write> {
+    stdout: &'a mut stdio::Out<O, stdio::behavior::Ignore>,
+    stderr: &'a mut stdio::Out<E, stdio::behavior::Ignore>,
+    state: Option<&'a mut state::State>,
+    state_path: &'a path::Path,
+}
+
+impl<O: io::Write, E: io::Write> novel::Progress for Report<'_, O, E> {
+    fn downloading(&mut self, novel: &novel::Novel, idx: usize) {
+        self.stdout.write_fmt(format_args!(">>>{}: Downloading...", novel.chapter_url(idx)));
+    }
+
+    fn downloaded(&mut self, _: &novel::Novel, _: &novel::Chapter) {
+        self.stdout.write_fmtn(format_args!("OK"));
+    }
+
+    fn failed(&mut self, _: &novel::Novel, _: usize, error: &error::Error) {
+        self.stdout.write_fmtn(format_args!("ERR"));
+        self.stderr.write_fmtn(format_args!("{error}"));
+    }
+
+    fn retry(&mut self, count: usize) {
+        self.stdout.write_fmtn(format_args!("Retry failed chapters: {count}"));
+    }
+
+    fn checkpoint(&mut self, novel: &novel::Novel, idx: Option<usize>, end: u64) -> Result<(), error::Error> {
+        match self.state.as_mut() {
+            Some(state) => {
+                if let Some(idx) = idx {
+                    state.chapters.push(state::Chapter {
+                        id: novel.index.chapters[idx].id.clone(),
+                        offset: state.end,
+                    });
+                }
+                state.end = end;
+                state.save(self.state_path).map_err(|error| error::Error::output(Some(self.state_path), error))
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+fn dump(io: &stdio::Io, http: &Arc<http::Client>, args: &cli::Cli, novel: &cli::Novel) -> Outcome {
+    let mut stderr = io.stderr().ignore_errors();
+    let mut stdout = io.stdout().ignore_errors();
+
+    let novel_url = format!("https://kakuyomu.jp/works/{}", novel.id);
+    let quiet = args.is_quiet();
+    if !quiet {
+        stdout.write_fmt(format_args!(">>>{novel_url}: Fetch novel index..."));
+    }
+    let dumper = novel::Dumper::new(http.clone()).with_jobs(args.jobs);
+    let fetched = match dumper.fetch(&novel.id) {
+        Ok(fetched) => {
+            if !quiet {
+                stdout.write_fmtn(format_args!("OK"));
+            }
+            fetched
+        }
+        Err(error) => {
+            if !quiet && matches!(error, error::Error::NotFound { .. } | error::Error::Network { .. } | error::Error::Session { .. }) {
+                stdout.write_fmtn(format_args!("ERR"));
+            }
+            stderr.write_fmtn(format_args!("{error}"));
+            return Outcome::from(&error)
         }
     };
+    let index = &fetched.index;
 
-    let novel_file_name = match args.out {
+    let work = &index.work;
+    if args.info {
+        if args.json {
+            match serde_json::to_string_pretty(work) {
+                Ok(work) => stdout.write_fmtn(format_args!("{work}")),
+                Err(error) => {
+                    stderr.write_fmtn(format_args!("Unable to serialize work: {error}"));
+                    return Outcome::Failure
+                }
+            }
+        } else {
+            print_info(&mut stdout, &novel_url, index);
+        }
+        return Outcome::Success
+    }
+
+    stdout.write_fmtn(format_args!("Title: {}", work.title));
+    if let Some(author) = work.author.as_ref() {
+        stdout.write_fmtn(format_args!("Author: {}", author));
+    }
+    if let Some(status) = work.status {
+        stdout.write_fmtn(format_args!("Status: {}", status));
+    }
+
+    let novel_file_name = match args.out.as_ref() {
         Some(out) => path::PathBuf::from(out),
-        None => construct_file_path(".", title),
+        None if args.split => path::Path::new(".").join(&work.title),
+        None => construct_file_path(".", &work.title, args.format),
     };
     stdout.write_fmtn(format_args!("Number of chapters: {}", index.chapters.len()));
+    if !index.sections.is_empty() {
+        stdout.write_fmtn(format_args!("Number of sections: {}", index.sections.len()));
+    }
 
-    let min = args.from.get();
-    stdout.write_fmtn(format_args!("Download chapters: {}..{}", min, max));
-
-    let mut novel_out = match fs::OpenOptions::new().create(true).write(true).truncate(true).open(&novel_file_name) {
-        Ok(novel_out) => novel_out,
+    let selected = match args.select.as_ref() {
+        Some(select) => select.select(index),
+        None => select::Selection::range(args.from, args.to).select(index),
+    };
+    let mut selected = match selected {
+        Ok(selected) => selected,
         Err(error) => {
-            stderr.write_fmtn(format_args!("{}: Cannot write: {error}", novel_file_name.display()));
-            return ExitCode::FAILURE
+            stderr.write_fmtn(format_args!("{error}"));
+            return Outcome::Failure
         }
     };
-
-    macro_rules! write_novel {
-        ($($arg:tt)*) => {
-            if let Err(error) = std::io::Write::write_fmt(&mut novel_out, format_args!($($arg)*)) {
-                stderr.write_fmtn(format_args!("{}: Cannot write: {error}", novel_file_name.display()));
-                return ExitCode::FAILURE
+    if let Some(episode) = novel.episode.as_ref() {
+        match index.chapters.iter().position(|chapter| chapter.id == *episode) {
+            Some(start) => selected.retain(|idx| *idx >= start),
+            None => {
+                stderr.write_fmtn(format_args!("Novel has no episode '{}'", episode));
+                return Outcome::Failure
             }
-        };
+        }
+    }
+    if let Some(since) = args.since.as_ref() {
+        selected.retain(|idx| index.chapters[*idx].published_at.as_deref().is_some_and(|date| since.precedes(date)));
+    }
+    if let Some(until) = args.until.as_ref() {
+        selected.retain(|idx| index.chapters[*idx].published_at.as_deref().is_some_and(|date| until.follows(date)));
+    }
+    if selected.is_empty() && args.update.is_none() {
+        stderr.write_fmtn(format_args!("No chapters match selection"));
+        return Outcome::Failure
+    }
+    stdout.write_fmtn(format_args!("Download chapters: {}", format_ranges(&selected)));
+
+    if args.resume && args.update.is_some() {
+        stderr.write_fmtn(format_args!("Options --resume and --update cannot be used together"));
+        return Outcome::Failure
+    } else if args.split && !args.format.is_splittable() {
+        stderr.write_fmtn(format_args!("Format {:?} cannot be split", args.format));
+        return Outcome::Failure
+    } else if (args.resume || args.update.is_some()) && (args.split || !args.format.is_resumable()) {
+        stderr.write_fmtn(format_args!("Format {:?} cannot be continued", args.format));
+        return Outcome::Failure
     }
 
-    write_novel!("{}\n===================\n", title);
+    //Options, that affect only some formats
+    let sjis = args.sjis && args.format == render::Format::Aozora;
+    let wrap = args.wrap.filter(|_| args.format == render::Format::Text);
+    let state_path = state::State::path(&novel_file_name);
+    let mut state = if args.resume || args.update.is_some() {
+        match state::State::load(&state_path) {
+            Ok(state) => if state.novel != novel.id {
+                stderr.write_fmtn(format_args!("{}: Cannot continue: output belongs to novel '{}'", novel_file_name.display(), state.novel));
+                return Outcome::Failure
+            } else if state.format != args.format || state.ruby != args.ruby {
+                stderr.write_fmtn(format_args!("{}: Cannot continue: output is written with format={:?} and ruby={:?}", novel_file_name.display(), state.format, state.ruby));
+                return Outcome::Failure
+            } else if state.sjis != sjis || state.wrap != wrap {
+                stderr.write_fmtn(format_args!("{}: Cannot continue: output is written with sjis={} and wrap={:?}", novel_file_name.display(), state.sjis, state.wrap));
+                return Outcome::Failure
+            } else {
+                Some(state)
+            },
+            Err(error) => {
+                stderr.write_fmtn(format_args!("{}: Cannot continue: {error}", state_path.display()));
+                return Outcome::Failure
+            }
+        }
+    } else {
+        None
+    };
 
-    write_novel!("Original: {novel_url}\n");
+    let chapters: Vec<usize> = match state.as_ref() {
+        //Download every chapter that is not written yet
+        Some(state) if args.update.is_some() => selected.into_iter().filter(|idx| !state.contains(&index.chapters[*idx].id)).collect(),
+        //Continue after the furthest written chapter, as retried chapters are written out of order
+        Some(state) => match state.chapters.last() {
+            Some(last) => match index.chapters.iter().rposition(|chapter| state.contains(&chapter.id)) {
+                Some(furthest) => selected.into_iter().filter(|idx| *idx > furthest && !state.contains(&index.chapters[*idx].id)).collect(),
+                None => {
+                    stderr.write_fmtn(format_args!("{}: Cannot resume: chapter '{}' is no longer present in novel", novel_file_name.display(), last.id));
+                    return Outcome::Failure
+                }
+            },
+            None => selected,
+        },
+        None => selected,
+    };
 
-    let max_idx = max - 1;
-    let selectors = html::ChapterSelector::new();
-    for (idx, chapter) in index.chapters.into_iter().enumerate().skip(min - 1) {
-        if idx > max_idx {
-            break;
+    if args.dry_run {
+        let sections: Vec<&str> = (0..index.chapters.len()).map(|idx| match index.sections_before(idx, true).last() {
+            Some(section) => index.sections[*section].title.as_str(),
+            None => "",
+        }).collect();
+        let section_width = sections.iter().map(|section| section.width()).max().unwrap_or(0).max("Section".len());
+
+        stdout.write_fmtn(format_args!("-------------------"));
+        stdout.write_fmtn(format_args!("     No. Published      Chars  Section{:pad$}  Title", "", pad = section_width - "Section".len()));
+        for (idx, (chapter, section)) in index.chapters.iter().zip(sections.iter()).enumerate() {
+            let mark = if chapters.binary_search(&idx).is_ok() { '*' } else { ' ' };
+            let published_at = match chapter.published_at.as_deref().and_then(select::Date::of_timestamp) {
+                Some(date) => date.to_string(),
+                None => "-".to_owned(),
+            };
+            let characters = match chapter.characters {
+                Some(characters) => characters.to_string(),
+                None => "-".to_owned(),
+            };
+            let title = chapter.title.as_deref().unwrap_or("-");
+            stdout.write_fmtn(format_args!("{mark} {:>6} {published_at:<10} {characters:>9}  {section}{:pad$}  {title}", idx + 1, "", pad = section_width - section.width()));
         }
+        stdout.write_fmtn(format_args!("-------------------"));
+        stdout.write_fmtn(format_args!("Selected {} chapters: {}", chapters.len(), format_ranges(&chapters)));
+        return Outcome::Success
+    }
 
-        url.push_str(&novel_url);
-        url.push_str("/episodes/");
-        url.push_str(&chapter);
+    if args.update.is_some() {
+        if chapters.is_empty() {
+            stdout.write_fmtn(format_args!("No new chapters"));
+            return Outcome::Success
+        }
+        stdout.write_fmtn(format_args!("New chapters: {}", chapters.len()));
+    }
 
-        stdout.write_fmt(format_args!(">>>{url}: Downloading..."));
-        let body: String = match http.get(&url) {
-            Ok(body) => body,
-            Err(error) => {
-                stdout.write_fmt(format_args!("ERR"));
-                stderr.write_fmtn(format_args!("{error}"));
-                continue
+    macro_rules! write_novel {
+        ($result:expr) => {
+            match $result {
+                Ok(result) => result,
+                Err(error) => {
+                    stderr.write_fmtn(format_args!("{}", error::Error::output(Some(&novel_file_name), error)));
+                    return Outcome::Failure
+                }
             }
         };
-        let chapter = html::Document::new(&body);
-        let (title, body) = match chapter.get_chapter_content(&selectors) {
-            Some(result) => result,
-            None => {
-                stdout.write_fmtn(format_args!("ERR"));
-                stderr.write_fmtn(format_args!("!!!Cannot find chapter content"));
-                return ExitCode::FAILURE
+    }
+
+    let novel_out = match state.as_ref() {
+        _ if args.split => Ok(None),
+        Some(state) => fs::OpenOptions::new().write(true).open(&novel_file_name).and_then(|mut novel_out| {
+            novel_out.set_len(state.end)?;
+            io::Seek::seek(&mut novel_out, io::SeekFrom::End(0))?;
+            Ok(novel_out)
+        }).map(Some),
+        None => fs::OpenOptions::new().create(true).write(true).truncate(true).open(&novel_file_name).map(Some),
+    };
+    let novel_out = write_novel!(novel_out).map(io::BufWriter::new);
+
+    let novel_out: Box<dyn render::Render> = match (novel_out, args.format) {
+        (None, _) => Box::new(write_novel!(render::Directory::new(novel_file_name.clone(), args.ruby))),
+        (Some(novel_out), render::Format::Markdown) => Box::new(render::Markdown::new(novel_out, args.ruby)),
+        (Some(novel_out), render::Format::Text) => Box::new(render::Text::new(novel_out, args.ruby, args.wrap)),
+        (Some(novel_out), render::Format::Aozora) => Box::new(render::Aozora::new(novel_out, args.sjis, index, &fetched.url)),
+        (Some(novel_out), render::Format::Json) => Box::new(render::Json::new(novel_out, false)),
+        (Some(novel_out), render::Format::Ndjson) => Box::new(render::Json::new(novel_out, true)),
+        (Some(novel_out), render::Format::Html) => Box::new(render::Html::new(novel_out, args.vertical)),
+        (Some(novel_out), render::Format::Epub) => Box::new(write_novel!(render::Epub::new(novel_out, args.ruby))),
+    };
+
+    macro_rules! save_state {
+        ($state:expr) => {
+            if let Err(error) = $state.save(&state_path) {
+                stderr.write_fmtn(format_args!("{}", error::Error::output(Some(&state_path), error)));
+                return Outcome::Failure
             }
         };
-        stdout.write_fmtn(format_args!("OK"));
-        match title {
-            Some(title) => write_novel!("\n{title}\n-------------------\n"),
-            None => write_novel!("\nChapter {}\n-------------------\n", idx + 1),
-        }
-        for line in body {
-            match line {
-                html::Line::Break => write_novel!("<br/>\n"),
-                html::Line::Paragraph(line) => write_novel!("{}\n\n", line.inner_html()),
-            }
-        }
+    }
 
-        url.clear();
+    //Chapters, that are already within the output
+    let written = state.clone();
+    match written.as_ref() {
+        Some(_) => if let Some(idx) = chapters.first() {
+            stdout.write_fmtn(format_args!("Continue from chapter: {}", idx + 1));
+        },
+        //Output end is recorded once header is written
+        None if args.format.is_resumable() && !args.split => state = Some(state::State {
+            novel: novel.id.clone(),
+            out: novel_file_name.clone(),
+            format: args.format,
+            ruby: args.ruby,
+            sjis,
+            wrap,
+            chapters: Vec::new(),
+            end: 0,
+        }),
+        None => (),
     }
 
-    if let Err(error) = std::io::Write::flush(&mut novel_out) {
-        stderr.write_fmtn(format_args!("{}: Cannot write: {error}", novel_file_name.display()));
-        return ExitCode::FAILURE
+    let result = {
+        let mut report = Report {
+            stdout: &mut stdout,
+            stderr: &mut stderr,
+            state: state.as_mut(),
+            state_path: &state_path,
+        };
+        match written.as_ref() {
+            Some(written) => dumper.append(&fetched, chapters, &|id| written.contains(id), novel_out, &mut report),
+            None => dumper.dump(&fetched, chapters, novel_out, &mut report),
+        }
+    };
+    let failed = match result {
+        Ok(failed) => failed,
+        //Renderer doesn't know its path
+        Err(error::Error::Output { path: None, error }) => write_novel!(Err(error)),
+        Err(error @ error::Error::Output { .. }) => {
+            stderr.write_fmtn(format_args!("{error}"));
+            return Outcome::Failure
+        },
+        Err(error) => {
+            stdout.write_fmtn(format_args!("ERR"));
+            stderr.write_fmtn(format_args!("!!!{error}"));
+            return Outcome::from(&error)
+        },
+    };
+
+    if let Some(state) = state.as_mut() {
+        //Update and retries write chapters out of order
+        write_novel!(state.reorder(&novel_file_name, index));
+        save_state!(state);
     }
     stdout.write_fmtn(format_args!("-------------------"));
     stdout.write_fmtn(format_args!("Output: {}", novel_file_name.display()));
-    stdout.write_fmtn(format_args!("Pandoc command to generate EPUB:\npandoc --embed-resources --standalone --shift-heading-level-by=-1 --from=gfm -o novel.epub \"{}\"", novel_file_name.display()));
-    ExitCode::SUCCESS
+    if args.format == render::Format::Markdown && !args.split {
+        stdout.write_fmtn(format_args!("Pandoc command to generate EPUB:\npandoc --embed-resources --standalone --shift-heading-level-by=-1 --from=gfm -o novel.epub \"{}\"", novel_file_name.display()));
+    }
+
+    if !failed.is_empty() {
+        stderr.write_fmtn(format_args!("-------------------"));
+        stderr.write_fmtn(format_args!("Failed to download {} chapters:", failed.len()));
+        for idx in failed {
+            let chapter = &index.chapters[idx];
+            stderr.write_fmtn(format_args!("{}. {} {novel_url}/episodes/{}", idx + 1, chapter.title.as_deref().unwrap_or("-"), chapter.id));
+        }
+
+        return Outcome::Incomplete
+    }
+
+    Outcome::Success
 }
@@ -0,0 +1,337 @@
+#![allow(clippy::result_large_err)]
+//!Library API to download novels
+use core::num::NonZeroUsize;
+use std::io;
+use std::sync::Arc;
+
+use crate::{html, http};
+use crate::error::Error;
+use crate::render::Render;
+
+///Novel with its table of contents
+pub struct Novel {
+    pub id: String,
+    pub url: String,
+    pub index: html::Index,
+}
+
+impl Novel {
+    ///Fetches novel's table of contents
+    pub fn fetch(http: &http::Client, id: &str) -> Result<Self, Error> {
+        let url = format!("https://kakuyomu.jp/works/{id}");
+        let body: String = match http.get(&url) {
+            Ok(body) => body,
+            Err(error) => return Err(Error::http(url, None, error)),
+        };
+
+        let index = match html::Document::new(&body).get_index() {
+            Some(Ok(index)) => index,
+            Some(Err(error)) => return Err(Error::Parse {
+                url,
+                error,
+            }),
+            None => return Err(Error::LayoutChanged {
+                url,
+                episode: None,
+                missing: "chapter index",
+            }),
+        };
+
+        Ok(Self {
+            id: id.to_owned(),
+            url,
+            index,
+        })
+    }
+
+    #[inline]
+    ///Returns URL of the chapter at position `idx`
+    pub fn chapter_url(&self, idx: usize) -> String {
+        format!("{}/episodes/{}", self.url, self.index.chapters[idx].id)
+    }
+
+    #[inline]
+    ///Starts downloading chapters at positions `chapters`, using up to `jobs` threads
+    pub fn chapters(&self, http: Arc<http::Client>, chapters: Vec<usize>, jobs: NonZeroUsize) -> Chapters<'_> {
+        let urls = chapters.iter().map(|idx| self.chapter_url(*idx)).collect();
+        Chapters {
+            novel: self,
+            positions: chapters.into_iter(),
+            downloads: http::Downloads::new(http, urls, jobs),
+        }
+    }
+}
+
+///Downloaded chapter
+///
+///Can be sent to other threads.
+pub struct Chapter {
+    ///Position within table of contents
+    pub idx: usize,
+    pub url: String,
+    pub title: Option<String>,
+    document: html::Document,
+    selectors: html::ChapterSelector,
+}
+
+impl Chapter {
+    ///Parses page of the chapter `id`
+    pub fn parse(idx: usize, id: &str, url: String, body: &str) -> Result<Self, Error> {
+        let document = html::Document::new(body);
+        let selectors = html::ChapterSelector::new();
+        let title = match document.get_chapter_content(&selectors) {
+            Some((title, _)) => title,
+            None => return Err(Error::LayoutChanged {
+                url,
+                episode: Some(id.to_owned()),
+                missing: "chapter content",
+            }),
+        };
+
+        Ok(Self {
+            idx,
+            url,
+            title,
+            document,
+            selectors,
+        })
+    }
+
+    #[inline(always)]
+    ///Returns chapter number, starting from 1
+    pub fn number(&self) -> usize {
+        self.idx + 1
+    }
+
+    #[inline]
+    ///Returns lines of the chapter's text
+    pub fn lines(&self) -> impl Iterator<Item = html::Line<'_>> + '_ {
+        self.document.get_chapter_lines(&self.selectors).into_iter().flatten()
+    }
+
+    #[inline]
+    ///Writes chapter into renderer
+    pub fn render(&self, out: &mut dyn Render) -> io::Result<()> {
+        out.chapter(self.number(), self.title.as_deref(), &mut self.lines())
+    }
+}
+
+///Iterator over chapters, yielded in requested order as soon as they are downloaded
+pub struct Chapters<'a> {
+    novel: &'a Novel,
+    positions: std::vec::IntoIter<usize>,
+    downloads: http::Downloads<String>,
+}
+
+impl Iterator for Chapters<'_> {
+    type Item = Result<Chapter, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let idx = self.positions.next()?;
+        let id = &self.novel.index.chapters[idx].id;
+        let url = self.novel.chapter_url(idx);
+        Some(match self.downloads.next()? {
+            Ok(body) => Chapter::parse(idx, id, url, &body),
+            Err(error) => Err(Error::http(url, Some(id.clone()), error)),
+        })
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.positions.size_hint()
+    }
+}
+
+///Receives progress of the dump
+///
+///All methods do nothing by default.
+pub trait Progress {
+    ///Chapter at position `idx` is being downloaded
+    fn downloading(&mut self, _novel: &Novel, _idx: usize) {
+    }
+
+    ///Chapter is downloaded and is about to be written
+    fn downloaded(&mut self, _novel: &Novel, _chapter: &Chapter) {
+    }
+
+    ///Chapter cannot be downloaded and is retried once after all other chapters
+    fn failed(&mut self, _novel: &Novel, _idx: usize, _error: &Error) {
+    }
+
+    ///Failed chapters are retried
+    fn retry(&mut self, _count: usize) {
+    }
+
+    ///Output is written up to `end`, after header or after chapter at position `idx`
+    ///
+    ///Called only for renderers, that can be continued. Error aborts the dump.
+    fn checkpoint(&mut self, _novel: &Novel, _idx: Option<usize>, _end: u64) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Progress for () {
+}
+
+///Writes chapters of the novels into renderer
+pub struct Dumper {
+    http: Arc<http::Client>,
+    jobs: NonZeroUsize,
+}
+
+impl Dumper {
+    #[inline]
+    ///Creates dumper, downloading one chapter at a time
+    pub fn new(http: Arc<http::Client>) -> Self {
+        Self {
+            http,
+            jobs: NonZeroUsize::MIN,
+        }
+    }
+
+    ///Sets number of chapters to download in parallel
+    pub fn with_jobs(mut self, jobs: NonZeroUsize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    #[inline]
+    ///Fetches novel's table of contents
+    pub fn fetch(&self, id: &str) -> Result<Novel, Error> {
+        Novel::fetch(&self.http, id)
+    }
+
+    #[inline]
+    ///Lazily downloads chapters at positions `chapters`
+    pub fn chapters<'a>(&self, novel: &'a Novel, chapters: Vec<usize>) -> Chapters<'a> {
+        novel.chapters(self.http.clone(), chapters, self.jobs)
+    }
+
+    #[inline]
+    ///Writes header and chapters at positions `chapters` into `out`, finishing it
+    ///
+    ///Chapters are written in reading order, including retried ones.
+    ///
+    ///Returns sorted positions of chapters, that could not be downloaded.
+    pub fn dump(&self, novel: &Novel, chapters: Vec<usize>, out: Box<dyn Render>, progress: &mut dyn Progress) -> Result<Vec<usize>, Error> {
+        self.write(novel, chapters, None, out, progress)
+    }
+
+    #[inline]
+    ///Writes chapters at positions `chapters` into continued output `out`, finishing it
+    ///
+    ///`written` tells whether episode with specified id is already within the output, to avoid repeating its sections.
+    ///Chapters are written after existing content, even if they precede it; see `state::State::reorder` to restore reading order.
+    ///
+    ///Returns sorted positions of chapters, that could not be downloaded.
+    pub fn append(&self, novel: &Novel, chapters: Vec<usize>, written: &dyn Fn(&str) -> bool, out: Box<dyn Render>, progress: &mut dyn Progress) -> Result<Vec<usize>, Error> {
+        self.write(novel, chapters, Some(written), out, progress)
+    }
+
+    fn write(&self, novel: &Novel, chapters: Vec<usize>, written: Option<&dyn Fn(&str) -> bool>, mut out: Box<dyn Render>, progress: &mut dyn Progress) -> Result<Vec<usize>, Error> {
+        let index = &novel.index;
+        let continued = written.is_some();
+
+        if !continued {
+            out.header(index, &novel.url)?;
+            if let Some(end) = out.checkpoint()? {
+                progress.checkpoint(novel, None, end)?;
+            }
+        }
+
+        //Headings are assigned as if all chapters, including ones already within the output, were written in reading order,
+        //so that retried chapters get their own headings. Headings of chapters, that fail, are left for update to write.
+        let mut all: Vec<usize> = match written {
+            Some(written) => index.chapters.iter().enumerate().filter(|(_, chapter)| written(&chapter.id)).map(|(idx, _)| idx).collect(),
+            None => Vec::new(),
+        };
+        all.extend(chapters.iter().copied());
+        all.sort_unstable();
+        all.dedup();
+        let mut headings = index.section_headings(&all);
+        let first_idx = chapters.first().copied();
+        let mut next_idx = None;
+
+        let mut write_chapter = |chapter: Chapter, out: &mut dyn Render, progress: &mut dyn Progress| -> Result<(), Error> {
+            let idx = chapter.idx;
+            //Chapter after a gap needs headings of its enclosing sections
+            let contiguous = next_idx == Some(idx);
+            if !contiguous && (continued || Some(idx) != first_idx) {
+                out.resume(index, idx)?;
+            }
+            next_idx = Some(idx + 1);
+
+            if let Ok(position) = all.binary_search(&idx) {
+                for section in core::mem::take(&mut headings[position]) {
+                    out.section(&index.sections[section])?;
+                }
+            }
+
+            chapter.render(out)?;
+
+            if let Some(end) = out.checkpoint()? {
+                progress.checkpoint(novel, Some(idx), end)?;
+            }
+            Ok(())
+        };
+
+        let mut failed = Vec::new();
+        //Chapters, that follow failed one, are held back until it is retried, to keep reading order
+        let mut pending = Vec::new();
+        let mut downloads = self.chapters(novel, chapters.clone());
+        for idx in chapters.iter().copied() {
+            progress.downloading(novel, idx);
+            let chapter = match downloads.next() {
+                Some(Ok(chapter)) => chapter,
+                Some(Err(error @ (Error::NotFound { .. } | Error::Network { .. }))) => {
+                    progress.failed(novel, idx, &error);
+                    failed.push(idx);
+                    continue
+                },
+                Some(Err(error)) => return Err(error),
+                None => break,
+            };
+            progress.downloaded(novel, &chapter);
+
+            if failed.is_empty() {
+                write_chapter(chapter, &mut *out, progress)?;
+            } else {
+                pending.push(chapter);
+            }
+        }
+
+        //Failed chapters are retried once, after all other chapters are downloaded
+        let mut pending = pending.into_iter().peekable();
+        if !failed.is_empty() {
+            progress.retry(failed.len());
+            let retried = core::mem::take(&mut failed);
+            let mut downloads = self.chapters(novel, retried.clone());
+            for idx in retried {
+                while let Some(chapter) = pending.next_if(|chapter| chapter.idx < idx) {
+                    write_chapter(chapter, &mut *out, progress)?;
+                }
+
+                progress.downloading(novel, idx);
+                match downloads.next() {
+                    Some(Ok(chapter)) => {
+                        progress.downloaded(novel, &chapter);
+                        write_chapter(chapter, &mut *out, progress)?;
+                    },
+                    Some(Err(error @ (Error::NotFound { .. } | Error::Network { .. }))) => {
+                        progress.failed(novel, idx, &error);
+                        failed.push(idx);
+                    },
+                    Some(Err(error)) => return Err(error),
+                    None => break,
+                }
+            }
+        }
+        for chapter in pending {
+            write_chapter(chapter, &mut *out, progress)?;
+        }
+
+        out.finish()?;
+        failed.sort_unstable();
+        Ok(failed)
+    }
+}
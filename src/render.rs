@@ -0,0 +1,142 @@
+//!Novel output formats
+use std::{io, time};
+use core::str::FromStr;
+
+use crate::{date, html};
+
+mod markdown;
+mod epub;
+mod directory;
+mod text;
+mod aozora;
+mod json;
+mod standalone;
+
+pub use markdown::Markdown;
+pub use text::Text;
+pub use aozora::Aozora;
+pub use json::Json;
+pub use standalone::Html;
+pub use epub::Epub;
+pub use directory::Directory;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde_derive::Serialize, serde_derive::Deserialize)]
+#[serde(rename_all = "lowercase")]
+///Output format
+pub enum Format {
+    ///GitHub flavored markdown
+    Markdown,
+    ///EPUB 3 book
+    Epub,
+    ///Plain UTF-8 text
+    Text,
+    ///Aozora Bunko (青空文庫) annotated text
+    Aozora,
+    ///Single JSON document
+    Json,
+    ///JSON document per episode, separated by newlines
+    Ndjson,
+    ///Self-contained HTML page
+    Html,
+}
+
+impl Format {
+    #[inline]
+    ///Returns file extension to use for the format
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Epub => "epub",
+            Self::Text => "txt",
+            Self::Aozora => "aozora.txt",
+            Self::Json => "json",
+            Self::Ndjson => "ndjson",
+            Self::Html => "html",
+        }
+    }
+
+    #[inline]
+    ///Returns whether output can be continued after interruption
+    pub const fn is_resumable(&self) -> bool {
+        match self {
+            Self::Markdown | Self::Text | Self::Aozora | Self::Ndjson => true,
+            Self::Epub | Self::Json | Self::Html => false,
+        }
+    }
+
+    #[inline]
+    ///Returns whether output can be split into file per chapter
+    pub const fn is_splittable(&self) -> bool {
+        match self {
+            Self::Markdown => true,
+            Self::Epub | Self::Text | Self::Aozora | Self::Json | Self::Ndjson | Self::Html => false,
+        }
+    }
+}
+
+impl Default for Format {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::Markdown
+    }
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if text.eq_ignore_ascii_case("md") || text.eq_ignore_ascii_case("markdown") {
+            Ok(Self::Markdown)
+        } else if text.eq_ignore_ascii_case("epub") {
+            Ok(Self::Epub)
+        } else if text.eq_ignore_ascii_case("txt") || text.eq_ignore_ascii_case("text") {
+            Ok(Self::Text)
+        } else if text.eq_ignore_ascii_case("aozora") {
+            Ok(Self::Aozora)
+        } else if text.eq_ignore_ascii_case("json") {
+            Ok(Self::Json)
+        } else if text.eq_ignore_ascii_case("ndjson") {
+            Ok(Self::Ndjson)
+        } else if text.eq_ignore_ascii_case("html") {
+            Ok(Self::Html)
+        } else {
+            Err(())
+        }
+    }
+}
+
+///Novel renderer
+pub trait Render {
+    ///Writes novel's header
+    fn header(&mut self, index: &html::Index, url: &str) -> io::Result<()>;
+    ///Writes section heading, preceding its chapters
+    fn section(&mut self, section: &html::Section) -> io::Result<()>;
+    ///Writes single chapter, `number` starts from 1
+    fn chapter(&mut self, number: usize, title: Option<&str>, lines: &mut dyn Iterator<Item = html::Line<'_>>) -> io::Result<()>;
+    ///Finalizes output
+    fn finish(self: Box<Self>) -> io::Result<()>;
+
+    ///Prepares to write chapter `idx` that doesn't follow previously written chapter.
+    ///
+    ///Called instead of writing header, when continuing existing output.
+    fn resume(&mut self, index: &html::Index, idx: usize) -> io::Result<()>;
+
+    ///Flushes output, returning its length, if output can be resumed from this point
+    fn checkpoint(&mut self) -> io::Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+///Formats current time as `CCYY-MM-DDThh:mm:ssZ`
+fn utc_now() -> String {
+    let secs = match time::SystemTime::now().duration_since(time::UNIX_EPOCH) {
+        Ok(now) => now.as_secs(),
+        Err(_) => 0,
+    };
+    let days = (secs / 86400) as i64;
+    let secs = secs % 86400;
+
+    let (year, month, day) = date::civil_from_days(days);
+
+    format!("{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z", secs / 3600, (secs % 3600) / 60, secs % 60)
+}
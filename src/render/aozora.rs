@@ -0,0 +1,227 @@
+use core::fmt::Write as FmtWrite;
+use std::io::{self, Write, Seek};
+
+use encoding_rs::{EncoderResult, SHIFT_JIS};
+
+use crate::html;
+use super::{Render, utc_now};
+
+const NOTATION: &str = "\
+-------------------------------------------------------
+【テキスト中に現れる記号について】
+
+《》：ルビ
+（例）漢字《かんじ》
+
+｜：ルビの付く文字列の始まりを特定する記号
+（例）｜東京《とうきょう》
+
+［＃］：入力者注　主に外字の説明や、傍点の位置の指定
+（例）［＃「傍点」に傍点］
+-------------------------------------------------------
+";
+
+///Writes text, replacing characters reserved by notation with their annotations
+fn escape(out: &mut String, text: &str) {
+    for ch in text.chars() {
+        match ch {
+            '《' => out.push_str("※［＃始め二重山括弧、1-1-52］"),
+            '》' => out.push_str("※［＃終わり二重山括弧、1-1-53］"),
+            '｜' => out.push_str("※［＃縦線、1-1-35］"),
+            '［' => out.push_str("※［＃始め角括弧、1-1-46］"),
+            '］' => out.push_str("※［＃終わり角括弧、1-1-47］"),
+            ch => out.push(ch),
+        }
+    }
+}
+
+///Returns heading annotation for nesting `depth`, starting from 1
+const fn heading(depth: u8) -> &'static str {
+    match depth {
+        0 | 1 => "大見出し",
+        2 => "中見出し",
+        _ => "小見出し",
+    }
+}
+
+///Aozora Bunko (青空文庫) annotated text writer
+pub struct Aozora<W> {
+    out: W,
+    //Encode in Shift_JIS with CRLF line endings
+    sjis: bool,
+    //Heading depth of chapters
+    depth: u8,
+    //Whether output is at the start of the page
+    page_start: bool,
+    title: String,
+    url: String,
+    published_at: Option<String>,
+    buffer: String,
+    bytes: Vec<u8>,
+}
+
+impl<W: Write> Aozora<W> {
+    #[inline]
+    ///Creates writer for work of `index` at `url`, encoding output in Shift_JIS with CRLF line endings, if `sjis` is set
+    ///
+    ///Work details are needed for the footer, even if output is continued.
+    pub fn new(out: W, sjis: bool, index: &html::Index, url: &str) -> Self {
+        Self {
+            out,
+            sjis,
+            depth: 2,
+            page_start: true,
+            title: index.work.title.clone(),
+            url: url.to_owned(),
+            published_at: index.work.published_at.clone(),
+            buffer: String::new(),
+            bytes: Vec::new(),
+        }
+    }
+
+    fn write_heading(&mut self, depth: u8, title: &str) {
+        let heading = heading(depth);
+        let _ = write!(self.buffer, "［＃{heading}］");
+        escape(&mut self.buffer, title);
+        let _ = write!(self.buffer, "［＃{heading}終わり］\n");
+    }
+
+    fn write_page_break(&mut self) {
+        if !self.page_start {
+            self.buffer.push_str("［＃改ページ］\n");
+        }
+        self.page_start = true;
+    }
+
+    ///Writes buffered text into output, encoding it if necessary
+    fn write_buffer(&mut self) -> io::Result<()> {
+        if !self.sjis {
+            self.out.write_all(self.buffer.as_bytes())?;
+            self.buffer.clear();
+            return Ok(());
+        }
+
+        let text = self.buffer.replace('\n', "\r\n");
+        let mut text = text.as_str();
+        let mut encoder = SHIFT_JIS.new_encoder();
+        self.bytes.clear();
+        loop {
+            if let Some(len) = encoder.max_buffer_length_from_utf8_without_replacement(text.len()) {
+                self.bytes.reserve(len);
+            }
+            let (result, read) = encoder.encode_from_utf8_to_vec_without_replacement(text, &mut self.bytes, true);
+            text = &text[read..];
+            match result {
+                EncoderResult::InputEmpty => break,
+                EncoderResult::OutputFull => continue,
+                //Characters outside of JIS X 0208 are annotated by code point
+                EncoderResult::Unmappable(ch) => {
+                    let note = format!("※［＃U+{:04X}］", ch as u32);
+                    let (note, _, _) = SHIFT_JIS.encode(&note);
+                    self.bytes.extend_from_slice(&note);
+                },
+            }
+        }
+
+        self.out.write_all(&self.bytes)?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> Render for Aozora<W> {
+    fn header(&mut self, index: &html::Index, _: &str) -> io::Result<()> {
+        escape(&mut self.buffer, &index.work.title);
+        self.buffer.push('\n');
+        if let Some(author) = index.work.author.as_deref() {
+            escape(&mut self.buffer, author);
+            self.buffer.push('\n');
+        }
+        self.buffer.push_str("\n");
+        self.buffer.push_str(NOTATION);
+        self.page_start = false;
+        self.write_buffer()
+    }
+
+    fn section(&mut self, section: &html::Section) -> io::Result<()> {
+        self.write_page_break();
+        self.depth = section.level.saturating_add(1);
+        self.write_heading(section.level, &section.title);
+        self.buffer.push('\n');
+        self.write_buffer()
+    }
+
+    fn chapter(&mut self, number: usize, title: Option<&str>, lines: &mut dyn Iterator<Item = html::Line<'_>>) -> io::Result<()> {
+        self.write_page_break();
+        match title {
+            Some(title) => self.write_heading(self.depth, title),
+            None => self.write_heading(self.depth, &format!("Chapter {number}")),
+        }
+        self.buffer.push('\n');
+
+        for line in lines {
+            match line {
+                html::Line::Break => self.buffer.push('\n'),
+                html::Line::Paragraph(line) => {
+                    for span in html::spans(line) {
+                        match span {
+                            html::Span::Text { text } => escape(&mut self.buffer, &text),
+                            html::Span::Ruby { base, reading } => {
+                                self.buffer.push('｜');
+                                escape(&mut self.buffer, &base);
+                                self.buffer.push('《');
+                                escape(&mut self.buffer, &reading);
+                                self.buffer.push('》');
+                            },
+                            html::Span::Emphasis { text } => {
+                                escape(&mut self.buffer, &text);
+                                self.buffer.push_str("［＃「");
+                                escape(&mut self.buffer, &text);
+                                self.buffer.push_str("」に傍点］");
+                            },
+                        }
+                    }
+                    self.buffer.push('\n');
+                },
+            }
+        }
+        self.page_start = false;
+
+        self.write_buffer()
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.buffer.push_str("\n\n\n");
+        let title = core::mem::take(&mut self.title);
+        self.buffer.push_str("底本：「");
+        escape(&mut self.buffer, &title);
+        self.buffer.push_str("」カクヨム\n");
+        let _ = write!(self.buffer, "　　　{}\n", self.url);
+        if let Some(published_at) = self.published_at.as_deref().and_then(|date| date.get(..10)) {
+            let mut date = published_at.split('-').map(|part| part.trim_start_matches('0'));
+            if let (Some(year), Some(month), Some(day)) = (date.next(), date.next(), date.next()) {
+                let _ = write!(self.buffer, "初出：{year}年{month}月{day}日\n");
+            }
+        }
+        let now = utc_now();
+        let mut date = now[..10].split('-').map(|part| part.trim_start_matches('0'));
+        if let (Some(year), Some(month), Some(day)) = (date.next(), date.next(), date.next()) {
+            let _ = write!(self.buffer, "{year}年{month}月{day}日作成\n");
+        }
+        self.write_buffer()?;
+        self.out.flush()
+    }
+
+    fn resume(&mut self, index: &html::Index, idx: usize) -> io::Result<()> {
+        if let Some(section) = index.sections_before(idx, true).last() {
+            self.depth = index.sections[*section].level.saturating_add(1);
+        }
+        self.page_start = false;
+        Ok(())
+    }
+
+    fn checkpoint(&mut self) -> io::Result<Option<u64>> {
+        self.out.flush()?;
+        self.out.stream_position().map(Some)
+    }
+}
@@ -0,0 +1,224 @@
+use std::{fs, io, path};
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::html::{self, escape};
+use super::Render;
+use super::markdown::{Heading, write_lines};
+
+const INDEX: &str = "index.md";
+const METADATA: &str = "metadata.json";
+//Limit of characters taken from title into file name
+const MAX_TITLE_LEN: usize = 64;
+
+///Writes `content` into `path`, unless file already has the same content
+fn write_if_changed(path: &path::Path, content: &[u8]) -> io::Result<()> {
+    match fs::read(path) {
+        Ok(existing) if existing == content => Ok(()),
+        _ => fs::write(path, content),
+    }
+}
+
+///Creates file name `<number>_<title>.md`, replacing characters that are not allowed in file names
+fn file_name(number: usize, title: Option<&str>) -> String {
+    let mut name = format!("{number:04}");
+    if let Some(title) = title {
+        name.push('_');
+        for ch in title.trim().chars().take(MAX_TITLE_LEN) {
+            match ch {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => name.push('_'),
+                ch if ch.is_whitespace() || ch.is_control() => name.push('_'),
+                ch => name.push(ch),
+            }
+        }
+    }
+    name.push_str(".md");
+    name
+}
+
+///Returns chapter number of file name created by `file_name`
+fn file_number(name: &str) -> Option<usize> {
+    let name = name.strip_suffix(".md")?;
+    let (number, _) = name.split_once('_').unwrap_or((name, ""));
+    if number.len() >= 4 && number.bytes().all(|byte| byte.is_ascii_digit()) {
+        number.parse().ok()
+    } else {
+        None
+    }
+}
+
+#[derive(serde_derive::Serialize)]
+struct Chapter {
+    number: usize,
+    id: String,
+    title: Option<String>,
+    published_at: Option<String>,
+    file: String,
+}
+
+#[derive(serde_derive::Serialize)]
+struct Metadata<'a> {
+    url: &'a str,
+    work: &'a serde_json::Value,
+    chapters: &'a [Chapter],
+}
+
+///Directory writer, placing each chapter into its own markdown file
+///
+///Index and metadata files are written on finish, listing every chapter within directory, including ones written before.
+///Files are only overwritten when their content changes, while files of the same chapter under previous title are removed.
+pub struct Directory {
+    path: path::PathBuf,
+    ruby: html::Ruby,
+    title: String,
+    url: String,
+    work: serde_json::Value,
+    index: Option<html::Index>,
+    chapters: Vec<Chapter>,
+    buffer: Vec<u8>,
+}
+
+impl Directory {
+    ///Creates writer, creating directory `path` if necessary
+    pub fn new(path: path::PathBuf, ruby: html::Ruby) -> io::Result<Self> {
+        fs::create_dir_all(&path)?;
+
+        Ok(Self {
+            path,
+            ruby,
+            title: String::new(),
+            url: String::new(),
+            work: serde_json::Value::Null,
+            index: None,
+            chapters: Vec::new(),
+            buffer: Vec::new(),
+        })
+    }
+
+    fn write_buffer(&mut self, name: &str) -> io::Result<()> {
+        write_if_changed(&self.path.join(name), &self.buffer)?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl Render for Directory {
+    fn header(&mut self, index: &html::Index, url: &str) -> io::Result<()> {
+        self.title = index.work.title.clone();
+        self.url = url.to_owned();
+        self.work = serde_json::to_value(&index.work).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        self.index = Some(index.clone());
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn section(&mut self, _: &html::Section) -> io::Result<()> {
+        //Sections are written into index according to chapters within directory
+        Ok(())
+    }
+
+    fn chapter(&mut self, number: usize, title: Option<&str>, lines: &mut dyn Iterator<Item = html::Line<'_>>) -> io::Result<()> {
+        let mut escaped = String::new();
+        match title {
+            Some(title) => escape(&mut escaped, title, false),
+            None => escaped = format!("Chapter {number}"),
+        }
+        write!(self.buffer, "{} {escaped}\n\n", Heading(1))?;
+        write_lines(&mut self.buffer, self.ruby, lines)?;
+
+        let file = file_name(number, title);
+        self.write_buffer(&file)?;
+
+        let episode = self.index.as_ref().and_then(|index| index.chapters.get(number - 1));
+        self.chapters.push(Chapter {
+            number,
+            id: episode.map(|episode| episode.id.clone()).unwrap_or_default(),
+            title: title.map(str::to_owned),
+            published_at: episode.and_then(|episode| episode.published_at.clone()),
+            file,
+        });
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        let index = self.index.take();
+        let mut files = HashMap::<usize, Vec<String>>::new();
+        for entry in fs::read_dir(&self.path)? {
+            if let Ok(name) = entry?.file_name().into_string() {
+                if let Some(number) = file_number(&name) {
+                    files.entry(number).or_default().push(name);
+                }
+            }
+        }
+
+        //Title of the chapter might have changed since it was written
+        for chapter in self.chapters.iter() {
+            for name in files.remove(&chapter.number).unwrap_or_default() {
+                if name != chapter.file {
+                    fs::remove_file(self.path.join(name))?;
+                }
+            }
+        }
+
+        //Chapters, written before, are listed with their current title, preferring file named after it
+        let (episodes, sections) = match index.as_ref() {
+            Some(index) => (&index.chapters[..], &index.sections[..]),
+            None => (&[][..], &[][..]),
+        };
+        for (idx, episode) in episodes.iter().enumerate() {
+            let number = idx + 1;
+            let file = file_name(number, episode.title.as_deref());
+            let file = match files.get(&number) {
+                Some(names) if names.contains(&file) => file,
+                Some(names) => names.iter().min().cloned().unwrap_or(file),
+                None => continue,
+            };
+            self.chapters.push(Chapter {
+                number,
+                id: episode.id.clone(),
+                title: episode.title.clone(),
+                published_at: episode.published_at.clone(),
+                file,
+            });
+        }
+        self.chapters.sort_by_key(|chapter| chapter.number);
+
+        let positions: Vec<usize> = self.chapters.iter().map(|chapter| chapter.number - 1).collect();
+        let headings = match index.as_ref() {
+            Some(index) => index.section_headings(&positions),
+            None => vec![Vec::new(); positions.len()],
+        };
+
+        write!(self.buffer, "{}\n===================\n", self.title)?;
+        write!(self.buffer, "Original: {}\n", self.url)?;
+        let mut escaped = String::new();
+        for (chapter, headings) in self.chapters.iter().zip(headings) {
+            for section in headings.into_iter().map(|section| &sections[section]) {
+                escaped.clear();
+                escape(&mut escaped, &section.title, false);
+                write!(self.buffer, "\n{} {escaped}\n\n", Heading(section.level.saturating_add(1)))?;
+            }
+            escaped.clear();
+            match chapter.title.as_deref() {
+                Some(title) => escape(&mut escaped, title, false),
+                None => escaped.push_str("Chapter"),
+            }
+            write!(self.buffer, "{}. [{escaped}](<{}>)\n", chapter.number, chapter.file)?;
+        }
+        self.write_buffer(INDEX)?;
+
+        let metadata = Metadata {
+            url: &self.url,
+            work: &self.work,
+            chapters: &self.chapters,
+        };
+        let metadata = serde_json::to_vec_pretty(&metadata).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        self.buffer = metadata;
+        self.write_buffer(METADATA)
+    }
+
+    #[inline(always)]
+    fn resume(&mut self, _: &html::Index, _: usize) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+use std::io::{self, Write, Seek};
+use core::fmt::Write as FmtWrite;
+
+use zip::write::SimpleFileOptions;
+use zip::CompressionMethod;
+
+use crate::html::{self, escape};
+use super::{Render, utc_now};
+
+const MIMETYPE: &str = "application/epub+zip";
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+const XHTML_START: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="ja" lang="ja">
+<head>
+<meta charset="UTF-8"/>
+"#;
+
+///Serializes children of the element as XHTML
+fn write_xhtml(out: &mut String, element: &scraper::ElementRef<'_>) {
+    for node in element.children() {
+        match node.value() {
+            scraper::Node::Text(text) => escape(out, text, true),
+            scraper::Node::Element(element) => {
+                let name = element.name();
+                out.push('<');
+                out.push_str(name);
+                for (key, value) in element.attrs() {
+                    out.push(' ');
+                    out.push_str(key);
+                    out.push_str("=\"");
+                    escape(out, value, true);
+                    out.push('"');
+                }
+                match scraper::ElementRef::wrap(node) {
+                    Some(child) if child.has_children() => {
+                        out.push('>');
+                        write_xhtml(out, &child);
+                        out.push_str("</");
+                        out.push_str(name);
+                        out.push('>');
+                    },
+                    _ => out.push_str("/>"),
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+struct Chapter {
+    file: String,
+    title: String,
+    ///Nesting level within table of contents, starting from 1
+    depth: u8,
+    ///Number of the chapter, that this entry precedes or is
+    number: usize,
+}
+
+///EPUB 3 book writer
+///
+///Chapters are written into archive as they come, while package document and navigation are written on finish.
+pub struct Epub<W: Write + Seek> {
+    zip: zip::ZipWriter<W>,
+    title: String,
+    author: Option<String>,
+    url: String,
+    chapters: Vec<Chapter>,
+    //Sections, written before next chapter
+    sections: Vec<Chapter>,
+    //Level of the last section
+    level: u8,
+    ruby: html::Ruby,
+    buffer: String,
+}
+
+impl<W: Write + Seek> Epub<W> {
+    pub fn new(out: W, ruby: html::Ruby) -> io::Result<Self> {
+        let mut zip = zip::ZipWriter::new(out);
+        //mimetype must be first and uncompressed
+        zip.start_file("mimetype", SimpleFileOptions::default().compression_method(CompressionMethod::Stored))?;
+        zip.write_all(MIMETYPE.as_bytes())?;
+        zip.start_file("META-INF/container.xml", Self::options())?;
+        zip.write_all(CONTAINER.as_bytes())?;
+
+        Ok(Self {
+            zip,
+            title: String::new(),
+            author: None,
+            url: String::new(),
+            chapters: Vec::new(),
+            sections: Vec::new(),
+            level: 0,
+            ruby,
+            buffer: String::new(),
+        })
+    }
+
+    #[inline(always)]
+    fn options() -> SimpleFileOptions {
+        SimpleFileOptions::default().compression_method(CompressionMethod::Deflated)
+    }
+
+    fn write_buffer(&mut self, name: &str) -> io::Result<()> {
+        self.zip.start_file(name, Self::options())?;
+        self.zip.write_all(self.buffer.as_bytes())?;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    fn start_xhtml(&mut self, title: &str) {
+        self.buffer.push_str(XHTML_START);
+        self.buffer.push_str("<title>");
+        escape(&mut self.buffer, title, true);
+        self.buffer.push_str("</title>\n</head>\n<body>\n");
+    }
+
+    #[inline(always)]
+    fn end_xhtml(&mut self) {
+        self.buffer.push_str("</body>\n</html>\n");
+    }
+
+    fn write_heading(&mut self, depth: u8, title: &str) {
+        //Title page is the only h1
+        let depth = depth.saturating_add(1).min(6);
+        let _ = write!(self.buffer, "<h{depth}>");
+        escape(&mut self.buffer, title, true);
+        let _ = write!(self.buffer, "</h{depth}>\n");
+    }
+}
+
+impl<W: Write + Seek> Render for Epub<W> {
+    fn header(&mut self, index: &html::Index, url: &str) -> io::Result<()> {
+        let title = index.work.title.as_str();
+        self.title = title.to_owned();
+        self.author = index.work.author.clone();
+        self.url = url.to_owned();
+
+        self.start_xhtml(title);
+        self.buffer.push_str("<h1>");
+        escape(&mut self.buffer, title, true);
+        self.buffer.push_str("</h1>\n");
+        if let Some(author) = index.work.author.as_deref() {
+            self.buffer.push_str("<p>");
+            escape(&mut self.buffer, author, true);
+            self.buffer.push_str("</p>\n");
+        }
+        self.buffer.push_str("<p>Original: <a href=\"");
+        escape(&mut self.buffer, url, true);
+        self.buffer.push_str("\">");
+        escape(&mut self.buffer, url, true);
+        self.buffer.push_str("</a></p>\n");
+        self.end_xhtml();
+        self.write_buffer("OEBPS/title.xhtml")
+    }
+
+    fn section(&mut self, section: &html::Section) -> io::Result<()> {
+        let file = format!("text/s{:04}.xhtml", self.chapters.len() + self.sections.len());
+        self.level = section.level;
+
+        self.start_xhtml(&section.title);
+        self.write_heading(section.level, &section.title);
+        self.end_xhtml();
+        self.write_buffer(&format!("OEBPS/{file}"))?;
+
+        self.sections.push(Chapter {
+            file,
+            title: section.title.clone(),
+            depth: section.level,
+            number: usize::MAX,
+        });
+        Ok(())
+    }
+
+    fn chapter(&mut self, number: usize, title: Option<&str>, lines: &mut dyn Iterator<Item = html::Line<'_>>) -> io::Result<()> {
+        let title = match title {
+            Some(title) => title.to_owned(),
+            None => format!("Chapter {number}"),
+        };
+        let file = format!("text/{number:04}.xhtml");
+
+        let depth = self.level.saturating_add(1);
+
+        self.start_xhtml(&title);
+        self.write_heading(depth, &title);
+        for line in lines {
+            match line {
+                html::Line::Break => self.buffer.push_str("<p><br/></p>\n"),
+                html::Line::Paragraph(line) => {
+                    self.buffer.push_str("<p>");
+                    match self.ruby {
+                        html::Ruby::Html => write_xhtml(&mut self.buffer, &line),
+                        ruby => html::write_text(&mut self.buffer, line, ruby),
+                    }
+                    self.buffer.push_str("</p>\n");
+                }
+            }
+        }
+        self.end_xhtml();
+        self.write_buffer(&format!("OEBPS/{file}"))?;
+
+        for mut section in self.sections.drain(..) {
+            section.number = number;
+            self.chapters.push(section);
+        }
+        self.chapters.push(Chapter {
+            file,
+            title,
+            depth,
+            number,
+        });
+        Ok(())
+    }
+
+    fn resume(&mut self, index: &html::Index, idx: usize) -> io::Result<()> {
+        self.level = match index.sections_before(idx, true).last() {
+            Some(section) => index.sections[*section].level,
+            None => 0,
+        };
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        let title = core::mem::take(&mut self.title);
+        //Chapters might be written out of order
+        let sections = core::mem::take(&mut self.sections);
+        self.chapters.extend(sections);
+        self.chapters.sort_by_key(|chapter| chapter.number);
+
+        self.start_xhtml(&title);
+        self.buffer.push_str("<nav epub:type=\"toc\" id=\"toc\">\n<h1>");
+        escape(&mut self.buffer, &title, true);
+        self.buffer.push_str("</h1>\n<ol>\n<li><a href=\"title.xhtml\">");
+        escape(&mut self.buffer, &title, true);
+        self.buffer.push_str("</a>");
+        let mut depth = 1;
+        for chapter in self.chapters.iter() {
+            //Nested list can only go one level deeper at a time
+            let chapter_depth = chapter.depth.max(1).min(depth + 1);
+            if chapter_depth > depth {
+                self.buffer.push_str("\n<ol>\n");
+                depth = chapter_depth;
+            } else {
+                self.buffer.push_str("</li>\n");
+                while depth > chapter_depth {
+                    self.buffer.push_str("</ol>\n</li>\n");
+                    depth -= 1;
+                }
+            }
+            let _ = write!(self.buffer, "<li><a href=\"{}\">", chapter.file);
+            escape(&mut self.buffer, &chapter.title, true);
+            self.buffer.push_str("</a>");
+        }
+        self.buffer.push_str("</li>\n");
+        while depth > 1 {
+            self.buffer.push_str("</ol>\n</li>\n");
+            depth -= 1;
+        }
+        self.buffer.push_str("</ol>\n</nav>\n");
+        self.end_xhtml();
+        self.write_buffer("OEBPS/nav.xhtml")?;
+
+        self.buffer.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        self.buffer.push_str("<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"uid\" xml:lang=\"ja\">\n");
+        self.buffer.push_str("<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n<dc:identifier id=\"uid\">");
+        escape(&mut self.buffer, &self.url, true);
+        self.buffer.push_str("</dc:identifier>\n<dc:title>");
+        escape(&mut self.buffer, &title, true);
+        self.buffer.push_str("</dc:title>\n");
+        if let Some(author) = self.author.take() {
+            self.buffer.push_str("<dc:creator>");
+            escape(&mut self.buffer, &author, true);
+            self.buffer.push_str("</dc:creator>\n");
+        }
+        self.buffer.push_str("<dc:language>ja</dc:language>\n<dc:source>");
+        escape(&mut self.buffer, &self.url, true);
+        self.buffer.push_str("</dc:source>\n");
+        let _ = write!(self.buffer, "<meta property=\"dcterms:modified\">{}</meta>\n</metadata>\n", utc_now());
+
+        self.buffer.push_str("<manifest>\n");
+        self.buffer.push_str("<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n");
+        self.buffer.push_str("<item id=\"title\" href=\"title.xhtml\" media-type=\"application/xhtml+xml\"/>\n");
+        for (idx, chapter) in self.chapters.iter().enumerate() {
+            let _ = write!(self.buffer, "<item id=\"c{idx}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n", chapter.file);
+        }
+        self.buffer.push_str("</manifest>\n<spine>\n<itemref idref=\"title\"/>\n");
+        for idx in 0..self.chapters.len() {
+            let _ = write!(self.buffer, "<itemref idref=\"c{idx}\"/>\n");
+        }
+        self.buffer.push_str("</spine>\n</package>\n");
+        self.write_buffer("OEBPS/content.opf")?;
+
+        self.zip.finish()?.flush()
+    }
+}
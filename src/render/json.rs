@@ -0,0 +1,178 @@
+use std::io::{self, Write, Seek};
+
+use crate::html;
+use super::Render;
+
+#[derive(serde_derive::Serialize)]
+struct TocSection<'a> {
+    title: &'a str,
+    level: u8,
+    ///Ids of episodes directly within section
+    episodes: Vec<&'a str>,
+    sections: Vec<TocSection<'a>>,
+}
+
+#[derive(serde_derive::Serialize)]
+///Table of contents
+struct Toc<'a> {
+    ///Ids of episodes outside of any section
+    episodes: Vec<&'a str>,
+    sections: Vec<TocSection<'a>>,
+}
+
+impl<'a> Toc<'a> {
+    fn new(index: &'a html::Index) -> Self {
+        let episodes = index.chapters.iter().enumerate().filter(|(idx, _)| !index.sections.iter().any(|section| section.chapters.contains(idx))).map(|(_, episode)| episode.id.as_str()).collect();
+        let mut toc = Self {
+            episodes,
+            sections: Vec::new(),
+        };
+
+        //Sections are listed in reading order, nested by level
+        let mut stack: Vec<TocSection<'a>> = Vec::new();
+        let mut close = |stack: &mut Vec<TocSection<'a>>, level: u8| {
+            while stack.last().map_or(false, |last| last.level >= level) {
+                if let Some(section) = stack.pop() {
+                    match stack.last_mut() {
+                        Some(parent) => parent.sections.push(section),
+                        None => toc.sections.push(section),
+                    }
+                }
+            }
+        };
+        for section in index.sections.iter() {
+            close(&mut stack, section.level);
+            stack.push(TocSection {
+                title: &section.title,
+                level: section.level,
+                episodes: index.chapters[section.chapters.clone()].iter().map(|episode| episode.id.as_str()).collect(),
+                sections: Vec::new(),
+            });
+        }
+        close(&mut stack, 0);
+
+        toc
+    }
+}
+
+#[derive(serde_derive::Serialize)]
+struct Episode<'a> {
+    number: usize,
+    id: &'a str,
+    title: Option<&'a str>,
+    published_at: Option<&'a str>,
+    ///Titles of enclosing sections, starting from outermost
+    sections: Vec<&'a str>,
+    ///Paragraphs as text fragments, empty for blank line
+    paragraphs: Vec<Vec<html::Span>>,
+}
+
+///JSON writer
+///
+///Writes single document with work's metadata, table of contents and episodes on finish,
+///or, in lines mode, streams each episode as a separate line (NDJSON).
+pub struct Json<W> {
+    out: W,
+    lines: bool,
+    url: String,
+    index: Option<html::Index>,
+    //Serialized episodes with their numbers
+    episodes: Vec<(usize, Vec<u8>)>,
+}
+
+impl<W: Write> Json<W> {
+    #[inline(always)]
+    ///Creates writer, streaming episode per line if `lines` is set
+    pub fn new(out: W, lines: bool) -> Self {
+        Self {
+            out,
+            lines,
+            url: String::new(),
+            index: None,
+            episodes: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write + Seek> Render for Json<W> {
+    fn header(&mut self, index: &html::Index, url: &str) -> io::Result<()> {
+        self.url = url.to_owned();
+        self.index = Some(index.clone());
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn section(&mut self, _: &html::Section) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn chapter(&mut self, number: usize, title: Option<&str>, lines: &mut dyn Iterator<Item = html::Line<'_>>) -> io::Result<()> {
+        let index = match self.index.as_ref() {
+            Some(index) => index,
+            None => return Err(io::Error::new(io::ErrorKind::Other, "Chapter is written before header")),
+        };
+        let idx = number - 1;
+        let episode = index.chapters.get(idx);
+        let episode = Episode {
+            number,
+            id: episode.map_or("", |episode| episode.id.as_str()),
+            title,
+            published_at: episode.and_then(|episode| episode.published_at.as_deref()),
+            sections: index.sections_before(idx, true).into_iter().map(|section| index.sections[section].title.as_str()).collect(),
+            paragraphs: lines.map(|line| match line {
+                html::Line::Paragraph(line) => html::spans(line),
+                html::Line::Break => Vec::new(),
+            }).collect(),
+        };
+
+        let mut episode = serde_json::to_vec(&episode)?;
+        if self.lines {
+            episode.push(b'\n');
+            self.out.write_all(&episode)
+        } else {
+            self.episodes.push((number, episode));
+            Ok(())
+        }
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        if !self.lines {
+            if let Some(index) = self.index.as_ref() {
+                //Retried chapters are written out of order
+                self.episodes.sort_by_key(|(number, _)| *number);
+
+                self.out.write_all(b"{\"url\":")?;
+                serde_json::to_writer(&mut self.out, &self.url)?;
+                self.out.write_all(b",\"work\":")?;
+                serde_json::to_writer(&mut self.out, &index.work)?;
+                self.out.write_all(b",\"toc\":")?;
+                serde_json::to_writer(&mut self.out, &Toc::new(index))?;
+                self.out.write_all(b",\"episodes\":[")?;
+                for (idx, (_, episode)) in self.episodes.iter().enumerate() {
+                    if idx > 0 {
+                        self.out.write_all(b",")?;
+                    }
+                    self.out.write_all(episode)?;
+                }
+                self.out.write_all(b"]}\n")?;
+            }
+        }
+        self.out.flush()
+    }
+
+    fn resume(&mut self, index: &html::Index, _: usize) -> io::Result<()> {
+        if self.index.is_none() {
+            self.index = Some(index.clone());
+        }
+        Ok(())
+    }
+
+    fn checkpoint(&mut self) -> io::Result<Option<u64>> {
+        if self.lines {
+            self.out.flush()?;
+            self.out.stream_position().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use core::fmt;
+use std::io::{self, Write, Seek};
+
+use crate::html::{self, escape};
+use super::Render;
+
+pub(super) struct Heading(pub(super) u8);
+
+impl fmt::Display for Heading {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for _ in 0..self.0 {
+            fmt.write_str("#")?;
+        }
+        Ok(())
+    }
+}
+
+///Writes chapter's text as markdown paragraphs
+pub(super) fn write_lines<W: Write>(out: &mut W, ruby: html::Ruby, lines: &mut dyn Iterator<Item = html::Line<'_>>) -> io::Result<()> {
+    let mut text = String::new();
+    for line in lines {
+        match line {
+            html::Line::Break => write!(out, "<br/>\n")?,
+            html::Line::Paragraph(line) => match ruby {
+                html::Ruby::Html => write!(out, "{}\n\n", line.inner_html())?,
+                ruby => {
+                    text.clear();
+                    html::write_text(&mut text, line, ruby);
+                    write!(out, "{}\n\n", text)?
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
+
+///GitHub flavored markdown writer
+pub struct Markdown<W> {
+    out: W,
+    ruby: html::Ruby,
+    //Heading level of chapters, when work is divided into sections
+    level: Option<u8>,
+    buffer: String,
+}
+
+impl<W: Write> Markdown<W> {
+    #[inline(always)]
+    pub fn new(out: W, ruby: html::Ruby) -> Self {
+        Self {
+            out,
+            ruby,
+            level: None,
+            buffer: String::new(),
+        }
+    }
+}
+
+impl<W: Write + Seek> Render for Markdown<W> {
+    fn header(&mut self, index: &html::Index, url: &str) -> io::Result<()> {
+        if !index.sections.is_empty() {
+            self.level = Some(2);
+        }
+        write!(self.out, "{}\n===================\n", index.work.title)?;
+        write!(self.out, "Original: {url}\n")
+    }
+
+    fn section(&mut self, section: &html::Section) -> io::Result<()> {
+        let level = section.level.saturating_add(1);
+        self.level = Some(level.saturating_add(1));
+        self.buffer.clear();
+        escape(&mut self.buffer, &section.title, false);
+        write!(self.out, "\n{} {}\n", Heading(level), self.buffer)
+    }
+
+    fn chapter(&mut self, number: usize, title: Option<&str>, lines: &mut dyn Iterator<Item = html::Line<'_>>) -> io::Result<()> {
+        let buffer = &mut self.buffer;
+        buffer.clear();
+        let title = title.map(|title| {
+            escape(buffer, title, false);
+            buffer.as_str()
+        });
+        match (self.level, title) {
+            (None, Some(title)) => write!(self.out, "\n{title}\n-------------------\n")?,
+            (None, None) => write!(self.out, "\nChapter {}\n-------------------\n", number)?,
+            (Some(level), Some(title)) => write!(self.out, "\n{} {title}\n", Heading(level))?,
+            (Some(level), None) => write!(self.out, "\n{} Chapter {}\n", Heading(level), number)?,
+        }
+        write_lines(&mut self.out, self.ruby, lines)
+    }
+
+    #[inline(always)]
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    fn resume(&mut self, index: &html::Index, idx: usize) -> io::Result<()> {
+        if !index.sections.is_empty() {
+            self.level = Some(match index.sections_before(idx, true).last() {
+                Some(section) => index.sections[*section].level.saturating_add(2),
+                None => 2,
+            });
+        }
+        Ok(())
+    }
+
+    fn checkpoint(&mut self) -> io::Result<Option<u64>> {
+        self.out.flush()?;
+        self.out.stream_position().map(Some)
+    }
+}
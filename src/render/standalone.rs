@@ -0,0 +1,256 @@
+use core::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+
+use crate::html::{self, escape};
+use super::Render;
+
+const STYLE: &str = "\
+body { margin: 0; font-family: serif; line-height: 1.8; }
+main { max-width: 42em; margin: 0 auto; padding: 1em; }
+p { margin: 0; }
+nav ol { list-style: none; }
+em.emphasis { font-style: normal; -webkit-text-emphasis: filled sesame; text-emphasis: filled sesame; }
+#vertical { position: fixed; top: 0.6em; right: 4em; }
+label[for=vertical] { position: fixed; top: 0.5em; right: 1em; font-family: sans-serif; }
+#vertical:checked ~ main { writing-mode: vertical-rl; max-width: none; height: calc(100vh - 4em); margin-top: 2em; overflow-x: auto; }
+#vertical:checked ~ main .tcy { -webkit-text-combine: horizontal; text-combine-upright: all; }
+";
+//Longest run of half-width digits, that is written horizontally within vertical text (縦中横)
+const MAX_TCY_DIGITS: usize = 2;
+
+#[inline(always)]
+fn is_tcy_neighbour(ch: Option<char>) -> bool {
+    match ch {
+        Some(ch) => ch.is_ascii_alphanumeric() || ch == '.' || ch == ',',
+        None => false,
+    }
+}
+
+///Writes escaped text, marking short standalone runs of half-width digits for tate-chu-yoko
+fn write_text(out: &mut String, text: &str) {
+    let mut rest = text;
+    while let Some(start) = rest.find(|ch: char| ch.is_ascii_digit()) {
+        let end = rest[start..].find(|ch: char| !ch.is_ascii_digit()).map_or(rest.len(), |end| start + end);
+        let (before, digits, after) = (&rest[..start], &rest[start..end], &rest[end..]);
+        escape(out, before, true);
+        if digits.len() <= MAX_TCY_DIGITS && !is_tcy_neighbour(before.chars().next_back()) && !is_tcy_neighbour(after.chars().next()) {
+            out.push_str("<span class=\"tcy\">");
+            out.push_str(digits);
+            out.push_str("</span>");
+        } else {
+            out.push_str(digits);
+        }
+        rest = after;
+    }
+    escape(out, rest, true);
+}
+
+struct Entry {
+    id: String,
+    title: String,
+    ///Nesting level within table of contents, starting from 1
+    depth: u8,
+    ///Number of the chapter, that this entry precedes or is
+    number: usize,
+    body: String,
+}
+
+///Self-contained HTML page writer
+///
+///Page is written on finish, as table of contents precedes chapters.
+pub struct Html<W> {
+    out: W,
+    vertical: bool,
+    title: String,
+    header: String,
+    entries: Vec<Entry>,
+    //Sections, written before next chapter
+    sections: Vec<Entry>,
+    //Level of the last section
+    level: u8,
+}
+
+impl<W: Write> Html<W> {
+    #[inline(always)]
+    ///Creates writer, using vertical writing (縦書き) by default, if `vertical` is set
+    pub fn new(out: W, vertical: bool) -> Self {
+        Self {
+            out,
+            vertical,
+            title: String::new(),
+            header: String::new(),
+            entries: Vec::new(),
+            sections: Vec::new(),
+            level: 0,
+        }
+    }
+}
+
+#[inline(always)]
+fn write_heading(out: &mut String, depth: u8, id: &str, title: &str) {
+    //Work title is the only h1
+    let depth = depth.saturating_add(1).min(6);
+    let _ = write!(out, "<h{depth} id=\"{id}\">");
+    escape(out, title, true);
+    let _ = write!(out, "</h{depth}>\n");
+}
+
+impl<W: Write> Render for Html<W> {
+    fn header(&mut self, index: &html::Index, url: &str) -> io::Result<()> {
+        self.title = index.work.title.clone();
+
+        self.header.push_str("<header>\n<h1>");
+        escape(&mut self.header, &index.work.title, true);
+        self.header.push_str("</h1>\n");
+        if let Some(author) = index.work.author.as_deref() {
+            self.header.push_str("<p class=\"author\">");
+            escape(&mut self.header, author, true);
+            self.header.push_str("</p>\n");
+        }
+        self.header.push_str("<p>Original: <a href=\"");
+        escape(&mut self.header, url, true);
+        self.header.push_str("\">");
+        escape(&mut self.header, url, true);
+        self.header.push_str("</a></p>\n</header>\n");
+        Ok(())
+    }
+
+    fn section(&mut self, section: &html::Section) -> io::Result<()> {
+        let id = format!("s{}", self.entries.len() + self.sections.len());
+        self.level = section.level;
+
+        let mut body = String::new();
+        write_heading(&mut body, section.level, &id, &section.title);
+        self.sections.push(Entry {
+            id,
+            title: section.title.clone(),
+            depth: section.level,
+            number: usize::MAX,
+            body,
+        });
+        Ok(())
+    }
+
+    fn chapter(&mut self, number: usize, title: Option<&str>, lines: &mut dyn Iterator<Item = html::Line<'_>>) -> io::Result<()> {
+        let title = match title {
+            Some(title) => title.to_owned(),
+            None => format!("Chapter {number}"),
+        };
+        let id = format!("e{number}");
+        let depth = self.level.saturating_add(1);
+
+        let mut body = String::new();
+        body.push_str("<article>\n");
+        write_heading(&mut body, depth, &id, &title);
+        for line in lines {
+            match line {
+                html::Line::Break => body.push_str("<p><br></p>\n"),
+                html::Line::Paragraph(line) => {
+                    body.push_str("<p>");
+                    for span in html::spans(line) {
+                        match span {
+                            html::Span::Text { text } => write_text(&mut body, &text),
+                            html::Span::Ruby { base, reading } => {
+                                body.push_str("<ruby>");
+                                write_text(&mut body, &base);
+                                body.push_str("<rp>（</rp><rt>");
+                                escape(&mut body, &reading, true);
+                                body.push_str("</rt><rp>）</rp></ruby>");
+                            },
+                            html::Span::Emphasis { text } => {
+                                body.push_str("<em class=\"emphasis\">");
+                                write_text(&mut body, &text);
+                                body.push_str("</em>");
+                            },
+                        }
+                    }
+                    body.push_str("</p>\n");
+                }
+            }
+        }
+        body.push_str("</article>\n");
+
+        for mut section in self.sections.drain(..) {
+            section.number = number;
+            self.entries.push(section);
+        }
+        self.entries.push(Entry {
+            id,
+            title,
+            depth,
+            number,
+            body,
+        });
+        Ok(())
+    }
+
+    fn resume(&mut self, index: &html::Index, idx: usize) -> io::Result<()> {
+        self.level = match index.sections_before(idx, true).last() {
+            Some(section) => index.sections[*section].level,
+            None => 0,
+        };
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        //Chapters might be written out of order
+        let sections = core::mem::take(&mut self.sections);
+        self.entries.extend(sections);
+        self.entries.sort_by_key(|entry| entry.number);
+
+        let mut page = String::new();
+        page.push_str("<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"utf-8\">\n");
+        page.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>");
+        escape(&mut page, &self.title, true);
+        page.push_str("</title>\n<style>\n");
+        page.push_str(STYLE);
+        page.push_str("</style>\n</head>\n<body>\n");
+        let checked = if self.vertical { " checked" } else { "" };
+        let _ = write!(page, "<input type=\"checkbox\" id=\"vertical\"{checked}>\n");
+        page.push_str("<label for=\"vertical\">縦書き</label>\n<main>\n");
+        page.push_str(&self.header);
+
+        page.push_str("<nav>\n<h2>目次</h2>\n<ol>");
+        let mut depth = 1;
+        for (idx, entry) in self.entries.iter().enumerate() {
+            //Nested list can only go one level deeper at a time
+            let entry_depth = if idx == 0 {
+                1
+            } else {
+                entry.depth.max(1).min(depth + 1)
+            };
+            if entry_depth > depth {
+                page.push_str("\n<ol>\n");
+                depth = entry_depth;
+            } else {
+                if idx > 0 {
+                    page.push_str("</li>");
+                }
+                page.push('\n');
+                while depth > entry_depth {
+                    page.push_str("</ol>\n</li>\n");
+                    depth -= 1;
+                }
+            }
+            let _ = write!(page, "<li><a href=\"#{}\">", entry.id);
+            escape(&mut page, &entry.title, true);
+            page.push_str("</a>");
+        }
+        if !self.entries.is_empty() {
+            page.push_str("</li>\n");
+        }
+        while depth > 1 {
+            page.push_str("</ol>\n</li>\n");
+            depth -= 1;
+        }
+        page.push_str("</ol>\n</nav>\n");
+
+        for entry in self.entries.iter() {
+            page.push_str(&entry.body);
+        }
+        page.push_str("</main>\n</body>\n</html>\n");
+
+        self.out.write_all(page.as_bytes())?;
+        self.out.flush()
+    }
+}
@@ -0,0 +1,186 @@
+use core::num::NonZeroUsize;
+use std::io::{self, Write, Seek};
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::html;
+use super::Render;
+
+///Characters that must not start a line (行頭禁則)
+const NO_LINE_START: &str = "、。，．・：；？！‼⁇⁈⁉ー‐゠–〜～）」』】〕〉》］｝〙〗〟’”ぁぃぅぇぉっゃゅょゎゕゖァィゥェォッャュョヮヵヶㇰㇱㇲㇳㇴㇵㇶㇷㇸㇹㇺㇻㇼㇽㇾㇿ…‥ヽヾゝゞ々〻,.:;!?)]}";
+///Characters that must not end a line (行末禁則)
+const NO_LINE_END: &str = "（「『【〔〈《［｛〘〖〝‘“([{";
+
+#[inline(always)]
+fn char_width(ch: char) -> usize {
+    //Ambiguous characters (e.g. `…`, `※`) are full-width in Japanese text
+    ch.width_cjk().unwrap_or(0)
+}
+
+///Writes `text` into `out`, breaking lines longer than `width` columns.
+///
+///Full-width and ambiguous width characters take 2 columns.
+///Characters, that must not start a line, are kept hanging past the limit, while opening brackets are moved to the next line.
+fn wrap(out: &mut String, text: &str, width: usize) {
+    let mut line = String::new();
+    let mut line_width = 0;
+
+    for ch in text.chars() {
+        let ch_width = char_width(ch);
+        if line_width + ch_width > width && !line.is_empty() && !NO_LINE_START.contains(ch) {
+            let mut split = line.len();
+            for (idx, last) in line.char_indices().rev() {
+                if idx == 0 || !NO_LINE_END.contains(last) {
+                    break;
+                }
+                split = idx;
+            }
+
+            out.push_str(&line[..split]);
+            out.push('\n');
+            line.drain(..split);
+            line_width = line.chars().map(char_width).sum();
+        }
+
+        line.push(ch);
+        line_width += ch_width;
+    }
+
+    out.push_str(&line);
+    out.push('\n');
+}
+
+///Plain text writer
+pub struct Text<W> {
+    out: W,
+    ruby: html::Ruby,
+    wrap: Option<NonZeroUsize>,
+    text: String,
+    buffer: String,
+}
+
+impl<W: Write> Text<W> {
+    #[inline(always)]
+    ///Creates writer, optionally wrapping lines at `wrap` columns.
+    ///
+    ///`Ruby::Html` is written as `Ruby::Paren`.
+    pub fn new(out: W, ruby: html::Ruby, wrap: Option<NonZeroUsize>) -> Self {
+        Self {
+            out,
+            ruby: match ruby {
+                html::Ruby::Html => html::Ruby::Paren,
+                ruby => ruby,
+            },
+            wrap,
+            text: String::new(),
+            buffer: String::new(),
+        }
+    }
+
+    fn write_line(&mut self, text: &str) -> io::Result<()> {
+        self.buffer.clear();
+        match self.wrap {
+            Some(width) => wrap(&mut self.buffer, text, width.get()),
+            None => {
+                self.buffer.push_str(text);
+                self.buffer.push('\n');
+            }
+        }
+        self.out.write_all(self.buffer.as_bytes())
+    }
+}
+
+impl<W: Write + Seek> Render for Text<W> {
+    fn header(&mut self, index: &html::Index, url: &str) -> io::Result<()> {
+        self.write_line(&index.work.title)?;
+        if let Some(author) = index.work.author.as_deref() {
+            self.write_line(author)?;
+        }
+        //URL is not wrapped to keep it usable
+        write!(self.out, "\nOriginal: {url}\n")
+    }
+
+    fn section(&mut self, section: &html::Section) -> io::Result<()> {
+        write!(self.out, "\n\n\n")?;
+        self.write_line(&section.title)
+    }
+
+    fn chapter(&mut self, number: usize, title: Option<&str>, lines: &mut dyn Iterator<Item = html::Line<'_>>) -> io::Result<()> {
+        write!(self.out, "\n\n")?;
+        match title {
+            Some(title) => self.write_line(title)?,
+            None => self.write_line(&format!("Chapter {number}"))?,
+        }
+        write!(self.out, "\n")?;
+
+        let mut text = core::mem::take(&mut self.text);
+        for line in lines {
+            match line {
+                html::Line::Break => write!(self.out, "\n")?,
+                html::Line::Paragraph(line) => {
+                    text.clear();
+                    html::write_plain_text(&mut text, line, self.ruby);
+                    self.write_line(&text)?;
+                }
+            }
+        }
+        self.text = text;
+
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    #[inline(always)]
+    fn resume(&mut self, _: &html::Index, _: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn checkpoint(&mut self) -> io::Result<Option<u64>> {
+        self.out.flush()?;
+        self.out.stream_position().map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::wrap;
+
+    fn wrapped(text: &str, width: usize) -> String {
+        let mut out = String::new();
+        wrap(&mut out, text, width);
+        out
+    }
+
+    #[test]
+    fn should_wrap_by_columns() {
+        assert_eq!(wrapped("abcdef", 4), "abcd\nef\n");
+        assert_eq!(wrapped("あいうえお", 6), "あいう\nえお\n");
+        assert_eq!(wrapped("あaいbう", 5), "あaい\nbう\n");
+        assert_eq!(wrapped("", 4), "\n");
+    }
+
+    #[test]
+    fn should_count_ambiguous_as_full_width() {
+        assert_eq!(wrapped("※○※○", 4), "※○\n※○\n");
+        assert_eq!(wrapped("あ―い", 4), "あ―\nい\n");
+    }
+
+    #[test]
+    fn should_hang_characters_that_cannot_start_line() {
+        assert_eq!(wrapped("あいう。え", 6), "あいう。\nえ\n");
+        assert_eq!(wrapped("あいう」。え", 6), "あいう」。\nえ\n");
+        assert_eq!(wrapped("あいう……", 6), "あいう……\n");
+    }
+
+    #[test]
+    fn should_move_opening_bracket_to_next_line() {
+        assert_eq!(wrapped("あい「う」", 6), "あい\n「う」\n");
+        assert_eq!(wrapped("あ「『う』」", 6), "あ\n「『う』」\n");
+        //Line is never left empty
+        assert_eq!(wrapped("「「「あ", 6), "「\n「「あ\n");
+    }
+}
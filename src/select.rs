@@ -0,0 +1,276 @@
+//!Episode selection expressions
+use core::fmt;
+use core::str::FromStr;
+use core::num::NonZeroUsize;
+
+use crate::{date, html};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Single item of the selection
+pub enum Item {
+    ///Range of chapter numbers, starting from 1, with optional start and end
+    Range(Option<NonZeroUsize>, Option<NonZeroUsize>),
+    ///Last chapters
+    Last(NonZeroUsize),
+    ///Episode id
+    Episode(String),
+    ///Section, which title contains text, including its sub-sections
+    Section(String),
+}
+
+impl FromStr for Item {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if let Some(last) = text.strip_prefix("last:") {
+            return last.trim().parse().map(Self::Last).map_err(|_| ());
+        } else if let Some(id) = text.strip_prefix("id:") {
+            let id = id.trim();
+            if id.is_empty() || !id.bytes().all(|byte| byte.is_ascii_digit()) {
+                return Err(());
+            }
+            return Ok(Self::Episode(id.to_owned()));
+        } else if let Some(section) = text.strip_prefix("section:") {
+            let section = section.trim();
+            if section.is_empty() {
+                return Err(());
+            }
+            return Ok(Self::Section(section.to_owned()));
+        }
+
+        let parse_bound = |bound: &str| -> Result<Option<NonZeroUsize>, ()> {
+            let bound = bound.trim();
+            if bound.is_empty() {
+                Ok(None)
+            } else {
+                bound.parse().map(Some).map_err(|_| ())
+            }
+        };
+        match text.split_once('-') {
+            Some((from, to)) => match (parse_bound(from)?, parse_bound(to)?) {
+                (Some(from), Some(to)) if from > to => Err(()),
+                (None, None) => Err(()),
+                (from, to) => Ok(Self::Range(from, to)),
+            },
+            None => match parse_bound(text)? {
+                Some(number) => Ok(Self::Range(Some(number), Some(number))),
+                None => Err(()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Range(Some(from), Some(to)) if from == to => write!(fmt, "{from}"),
+            Self::Range(from, to) => {
+                if let Some(from) = from {
+                    write!(fmt, "{from}")?;
+                }
+                fmt.write_str("-")?;
+                if let Some(to) = to {
+                    write!(fmt, "{to}")?;
+                }
+                Ok(())
+            },
+            Self::Last(last) => write!(fmt, "last:{last}"),
+            Self::Episode(id) => write!(fmt, "id:{id}"),
+            Self::Section(title) => write!(fmt, "section:{title}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+///Selection error
+pub enum Error {
+    ///Novel has no episode with specified id
+    NoEpisode(String),
+    ///Novel has no section containing specified text
+    NoSection(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoEpisode(id) => write!(fmt, "Novel has no episode '{id}'"),
+            Self::NoSection(title) => write!(fmt, "Novel has no section '{title}'"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Comma separated list of items, selecting union of their chapters.
+///
+///Items are chapter number (`15`), range (`1-10`, `40-`, `-5`), last chapters (`last:5`),
+///episode id (`id:1177354054883819763`) or section title (`section:第一章`).
+pub struct Selection {
+    pub items: Vec<Item>,
+}
+
+impl Selection {
+    #[inline]
+    ///Creates selection of chapters within range
+    pub fn range(from: Option<NonZeroUsize>, to: Option<NonZeroUsize>) -> Self {
+        Self {
+            items: vec![Item::Range(from, to)],
+        }
+    }
+
+    ///Returns sorted positions of selected chapters within `index`
+    ///
+    ///Ranges are limited by number of chapters.
+    pub fn select(&self, index: &html::Index) -> Result<Vec<usize>, Error> {
+        let len = index.chapters.len();
+        let mut selected = vec![false; len];
+
+        for item in self.items.iter() {
+            match item {
+                Item::Range(from, to) => {
+                    let from = from.map_or(0, |from| from.get() - 1);
+                    let to = to.map_or(len, |to| to.get().min(len));
+                    if from < to {
+                        selected[from..to].iter_mut().for_each(|selected| *selected = true);
+                    }
+                },
+                Item::Last(last) => {
+                    let from = len.saturating_sub(last.get());
+                    selected[from..].iter_mut().for_each(|selected| *selected = true);
+                },
+                Item::Episode(id) => match index.chapters.iter().position(|chapter| chapter.id == *id) {
+                    Some(idx) => selected[idx] = true,
+                    None => return Err(Error::NoEpisode(id.clone())),
+                },
+                Item::Section(title) => {
+                    let mut found = false;
+                    //Sub-sections follow their parent until next section of the same or higher level
+                    let mut level = None;
+                    for section in index.sections.iter() {
+                        match level {
+                            Some(level) if section.level > level => (),
+                            _ => level = if section.title.contains(title.as_str()) {
+                                Some(section.level)
+                            } else {
+                                None
+                            },
+                        }
+                        if level.is_some() {
+                            found = true;
+                            selected[section.chapters.clone()].iter_mut().for_each(|selected| *selected = true);
+                        }
+                    }
+                    if !found {
+                        return Err(Error::NoSection(title.clone()));
+                    }
+                },
+            }
+        }
+
+        Ok(selected.into_iter().enumerate().filter(|(_, selected)| *selected).map(|(idx, _)| idx).collect())
+    }
+}
+
+impl FromStr for Selection {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let items = text.split(',').filter(|item| !item.trim().is_empty()).map(Item::from_str).collect::<Result<Vec<_>, _>>()?;
+        if items.is_empty() {
+            Err(())
+        } else {
+            Ok(Self {
+                items,
+            })
+        }
+    }
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, item) in self.items.iter().enumerate() {
+            if idx > 0 {
+                fmt.write_str(",")?;
+            }
+            fmt::Display::fmt(item, fmt)?;
+        }
+        Ok(())
+    }
+}
+
+///Returns number of days since 1970-01-01 for the date in `YYYY-MM-DD` format
+fn parse_date(text: &str) -> Option<i64> {
+    let mut parts = text.split('-');
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(year), Some(month), Some(day), None) if year.len() == 4 && month.len() == 2 && day.len() == 2 && text.bytes().all(|byte| byte.is_ascii_digit() || byte == b'-') => {
+            let (year, month, day) = (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?);
+            if (1..=12).contains(&month) && (1..=31).contains(&day) {
+                Some(date::days_from_civil(year, month, day))
+            } else {
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+///Returns number of days since 1970-01-01 for the date in JST of UTC timestamp (e.g. `2024-01-01T15:00:00Z`)
+fn jst_day(timestamp: &str) -> Option<i64> {
+    let (date, time) = timestamp.split_once('T')?;
+    let hour: i64 = time.get(..2)?.parse().ok()?;
+    Some(parse_date(date)? + (hour + 9) / 24)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+///Date in `YYYY-MM-DD` format, in Japan Standard Time, as shown on the site
+pub struct Date {
+    text: String,
+    ///Days since 1970-01-01
+    day: i64,
+}
+
+impl Date {
+    ///Returns JST date of UTC timestamp (e.g. `2024-01-01T15:00:00Z`)
+    pub fn of_timestamp(timestamp: &str) -> Option<Self> {
+        let day = jst_day(timestamp)?;
+        let (year, month, date) = date::civil_from_days(day);
+        Some(Self {
+            text: format!("{year:04}-{month:02}-{date:02}"),
+            day,
+        })
+    }
+
+    #[inline]
+    ///Returns whether date is not later than JST date of UTC timestamp
+    pub fn precedes(&self, timestamp: &str) -> bool {
+        jst_day(timestamp).map_or(false, |day| self.day <= day)
+    }
+
+    #[inline]
+    ///Returns whether date is not earlier than JST date of UTC timestamp
+    pub fn follows(&self, timestamp: &str) -> bool {
+        jst_day(timestamp).map_or(false, |day| self.day >= day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        match parse_date(text) {
+            Some(day) => Ok(Self {
+                text: text.to_owned(),
+                day,
+            }),
+            None => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Date {
+    #[inline(always)]
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(&self.text)
+    }
+}
@@ -0,0 +1,112 @@
+//!Recording and replay of HTTP sessions
+//!
+//!Each response is stored as `<name>.json` with URL, status and headers, and `<name>.body` with its body,
+//!where name is derived from the URL.
+use core::time;
+use std::{fs, io, path};
+
+use crate::http::{Error, Response, Transport};
+
+#[derive(serde_derive::Serialize, serde_derive::Deserialize)]
+struct Entry {
+    url: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+///Returns file name for the response to `url`, without extension
+fn file_name(url: &str) -> String {
+    //FNV-1a, to keep names stable between builds
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in url.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+
+    let path = url.split_once("://").map_or(url, |(_, path)| path);
+    let path = path.split_once('/').map_or("", |(_, path)| path);
+    let mut name: String = path.chars().map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' }).take(100).collect();
+    if name.is_empty() {
+        name.push_str("index");
+    }
+    name.push_str(&format!("-{hash:08x}"));
+    name
+}
+
+///Transport, that stores every response of inner transport within directory
+pub struct Record<T> {
+    inner: T,
+    dir: path::PathBuf,
+}
+
+impl<T: Transport> Record<T> {
+    ///Creates transport, recording into `dir`, which is created if necessary
+    pub fn new(inner: T, dir: impl Into<path::PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            inner,
+            dir,
+        })
+    }
+
+    fn save(&self, url: &str, response: &Response) -> io::Result<()> {
+        let name = file_name(url);
+        let entry = Entry {
+            url: url.to_owned(),
+            status: response.status,
+            headers: response.headers.clone(),
+        };
+        fs::write(self.dir.join(format!("{name}.body")), &response.body)?;
+        fs::write(self.dir.join(format!("{name}.json")), serde_json::to_vec_pretty(&entry)?)
+    }
+}
+
+impl<T: Transport> Transport for Record<T> {
+    fn get(&self, url: &str, timeout: time::Duration) -> Result<Response, Error> {
+        let response = self.inner.get(url, timeout)?;
+        self.save(url, &response).map_err(Error::Session)?;
+        Ok(response)
+    }
+}
+
+///Transport, that serves responses from directory written by `Record`, without network
+pub struct Replay {
+    dir: path::PathBuf,
+}
+
+impl Replay {
+    #[inline]
+    ///Creates transport, serving responses from `dir`
+    pub fn new(dir: impl Into<path::PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+        }
+    }
+
+    fn load(&self, url: &str) -> io::Result<Response> {
+        let name = file_name(url);
+        let path = self.dir.join(format!("{name}.json"));
+        let entry: Entry = match fs::read(&path) {
+            Ok(entry) => serde_json::from_slice(&entry)?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Err(io::Error::new(io::ErrorKind::NotFound, format!("{}: No response recorded for {url}", path.display()))),
+            Err(error) => return Err(error),
+        };
+        if entry.url != url {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}: Recorded response is for {}", path.display(), entry.url)));
+        }
+
+        Ok(Response {
+            status: entry.status,
+            headers: entry.headers,
+            body: fs::read(self.dir.join(format!("{name}.body")))?,
+        })
+    }
+}
+
+impl Transport for Replay {
+    #[inline]
+    fn get(&self, url: &str, _: time::Duration) -> Result<Response, Error> {
+        self.load(url).map_err(Error::Session)
+    }
+}
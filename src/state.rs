@@ -0,0 +1,128 @@
+//!Dump progress state, persisted next to the output
+use core::num::NonZeroUsize;
+use std::{fs, io, path};
+use std::ffi::OsString;
+
+use crate::html;
+use crate::render::Format;
+
+///Extension of the state file, appended to the output path
+pub const EXTENSION: &str = ".state";
+
+#[derive(Clone, Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+///Chapter written to the output
+pub struct Chapter {
+    ///Episode id
+    pub id: String,
+    ///Offset within output where chapter starts, including its section headings
+    pub offset: u64,
+}
+
+#[derive(Clone, Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+///Progress of the novel dump
+pub struct State {
+    ///Novel id
+    pub novel: String,
+    ///Output path
+    pub out: path::PathBuf,
+    pub format: Format,
+    pub ruby: html::Ruby,
+    #[serde(default)]
+    ///Whether output is encoded in Shift_JIS
+    pub sjis: bool,
+    #[serde(default)]
+    ///Width, to which lines of text output are wrapped
+    pub wrap: Option<NonZeroUsize>,
+    ///Episodes in the order they are written to the output
+    pub chapters: Vec<Chapter>,
+    ///Length of the output after last written episode
+    pub end: u64,
+}
+
+impl State {
+    ///Returns path to the state of the specified output
+    pub fn path(out: &path::Path) -> path::PathBuf {
+        let mut path = OsString::from(out.as_os_str());
+        path.push(EXTENSION);
+        path.into()
+    }
+
+    ///Loads state from `path`
+    pub fn load(path: &path::Path) -> io::Result<Self> {
+        let state = fs::read(path)?;
+        serde_json::from_slice(&state).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    ///Saves state into `path`, replacing previous one
+    pub fn save(&self, path: &path::Path) -> io::Result<()> {
+        let state = serde_json::to_vec(self).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+        //Write via temporary file to avoid corrupting state when interrupted
+        let mut tmp = OsString::from(path.as_os_str());
+        tmp.push(".tmp");
+        fs::write(&tmp, state)?;
+        fs::rename(&tmp, path)
+    }
+
+    ///Returns whether episode is written to the output
+    pub fn contains(&self, id: &str) -> bool {
+        self.chapters.iter().any(|chapter| chapter.id == id)
+    }
+
+    ///Rearranges chapters within output file `out` according to their position in `index`
+    ///
+    ///Chapters, that are no longer present in the index, are kept after the chapter preceding them.
+    ///Header and content after the last chapter stay in place.
+    pub fn reorder(&mut self, out: &path::Path, index: &html::Index) -> io::Result<()> {
+        let mut order = Vec::with_capacity(self.chapters.len());
+        let mut position = 0;
+        for (idx, chapter) in self.chapters.iter().enumerate() {
+            if let Some(new_position) = index.chapters.iter().position(|episode| episode.id == chapter.id) {
+                position = new_position;
+            }
+            order.push((position, idx));
+        }
+        order.sort();
+
+        if order.iter().enumerate().all(|(idx, (_, old_idx))| idx == *old_idx) {
+            return Ok(());
+        }
+
+        let content = fs::read(out)?;
+        let end = self.end as usize;
+        if end > content.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Output is shorter than recorded in state"));
+        }
+
+        let mut result = Vec::with_capacity(content.len());
+        let mut chapters = Vec::with_capacity(self.chapters.len());
+        let start = self.chapters.first().map(|chapter| chapter.offset as usize).unwrap_or(end);
+        result.extend_from_slice(&content[..start]);
+        for (_, idx) in order {
+            let chapter = &self.chapters[idx];
+            let chapter_start = chapter.offset as usize;
+            let chapter_end = match self.chapters.get(idx + 1) {
+                Some(next) => next.offset as usize,
+                None => end,
+            };
+            if chapter_start > chapter_end || chapter_end > end {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Chapter offsets in state are invalid"));
+            }
+
+            chapters.push(Chapter {
+                id: chapter.id.clone(),
+                offset: result.len() as u64,
+            });
+            result.extend_from_slice(&content[chapter_start..chapter_end]);
+        }
+        result.extend_from_slice(&content[end..]);
+
+        let mut tmp = OsString::from(out.as_os_str());
+        tmp.push(".tmp");
+        fs::write(&tmp, result)?;
+        fs::rename(&tmp, out)?;
+
+        self.chapters = chapters;
+        Ok(())
+    }
+}
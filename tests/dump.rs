@@ -0,0 +1,126 @@
+use kakuyomu_dump::{html, http, novel, render};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::{fs, path};
+use core::time;
+
+const WORK: &str = include_str!("fixtures/work.html");
+const EPISODE: &str = include_str!("fixtures/episode.html");
+const WORK_URL: &str = "https://kakuyomu.jp/works/100";
+
+///Serves fixture pages, failing requests with 503 specified number of times
+struct Fixture {
+    failures: Mutex<HashMap<String, usize>>,
+}
+
+impl Fixture {
+    fn new(failures: &[(&str, usize)]) -> Self {
+        Self {
+            failures: Mutex::new(failures.iter().map(|(url, count)| (url.to_string(), *count)).collect()),
+        }
+    }
+}
+
+impl http::Transport for Fixture {
+    fn get(&self, url: &str, _: time::Duration) -> Result<http::Response, http::Error> {
+        if let Some(count) = self.failures.lock().unwrap().get_mut(url).filter(|count| **count > 0) {
+            *count -= 1;
+            return Ok(http::Response {
+                status: 503,
+                headers: Vec::new(),
+                body: Vec::new(),
+            });
+        }
+
+        let body = if url == WORK_URL {
+            Some(WORK.to_owned())
+        } else {
+            url.strip_prefix(WORK_URL).and_then(|path| path.strip_prefix("/episodes/")).filter(|id| ["1", "2", "3", "4"].contains(id)).map(|id| EPISODE.replace("TITLE", &format!("E{id}")))
+        };
+        Ok(match body {
+            Some(body) => http::Response {
+                status: 200,
+                headers: vec![("content-type".to_owned(), "text/html; charset=utf-8".to_owned())],
+                body: body.into_bytes(),
+            },
+            None => http::Response {
+                status: 404,
+                headers: Vec::new(),
+                body: Vec::new(),
+            },
+        })
+    }
+}
+
+fn dumper(fixture: Fixture) -> novel::Dumper {
+    let http = http::Client::new().with_transport(fixture).with_retry(http::Retry::none());
+    novel::Dumper::new(Arc::new(http))
+}
+
+///Dumps whole work into markdown file, returning its content
+fn dump_markdown(name: &str, failures: &[(&str, usize)]) -> (String, Vec<usize>) {
+    let dumper = dumper(Fixture::new(failures));
+    let novel = dumper.fetch("100").expect("fetch work");
+    let path = path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let out = fs::File::create(&path).expect("create output");
+    let out = render::Markdown::new(std::io::BufWriter::new(out), html::Ruby::Html);
+
+    let chapters = (0..novel.index.chapters.len()).collect();
+    let failed = dumper.dump(&novel, chapters, Box::new(out), &mut ()).expect("dump");
+
+    (fs::read_to_string(&path).expect("read output"), failed)
+}
+
+fn headings(markdown: &str) -> Vec<&str> {
+    markdown.lines().filter(|line| line.starts_with('#')).collect()
+}
+
+#[test]
+fn should_parse_index() {
+    let index = html::Document::new(WORK).get_index().expect("index").expect("parse index");
+
+    assert_eq!(index.work.id, "100");
+    assert_eq!(index.work.title, "Fixture work");
+    assert_eq!(index.work.author.as_deref(), Some("Author"));
+    //Order follows table of contents and episodes of other works are skipped
+    let chapters: Vec<_> = index.chapters.iter().map(|chapter| chapter.id.as_str()).collect();
+    assert_eq!(chapters, ["1", "2", "3", "4"]);
+    assert_eq!(index.chapters[2].title.as_deref(), Some("E3"));
+    let sections: Vec<_> = index.sections.iter().map(|section| (section.title.as_str(), section.level, section.chapters.clone())).collect();
+    assert_eq!(sections, [("SecA", 1, 0..2), ("SecB", 1, 2..4)]);
+}
+
+#[test]
+fn should_dump_markdown() {
+    let (markdown, failed) = dump_markdown("dump.md", &[]);
+
+    assert!(failed.is_empty());
+    assert!(markdown.starts_with("Fixture work\n===================\nOriginal: https://kakuyomu.jp/works/100\n"));
+    assert!(markdown.contains("Text of E3."));
+    assert_eq!(headings(&markdown), ["## SecA", "### E1", "### E2", "## SecB", "### E3", "### E4"]);
+}
+
+#[test]
+fn should_keep_sections_of_retried_chapter() {
+    let (expected, _) = dump_markdown("retry_expected.md", &[]);
+    let (markdown, failed) = dump_markdown("retry.md", &[("https://kakuyomu.jp/works/100/episodes/3", 1)]);
+
+    assert!(failed.is_empty());
+    assert_eq!(markdown, expected);
+}
+
+#[test]
+fn should_report_failed_chapter() {
+    let (markdown, failed) = dump_markdown("failed.md", &[("https://kakuyomu.jp/works/100/episodes/2", 2)]);
+
+    assert_eq!(failed, [1]);
+    assert_eq!(headings(&markdown), ["## SecA", "### E1", "## SecB", "### E3", "### E4"]);
+}
+
+#[test]
+fn should_send_chapter() {
+    fn is_send<T: Send>() {
+    }
+    is_send::<novel::Chapter>();
+}
@@ -0,0 +1,74 @@
+use kakuyomu_dump::http;
+
+use std::sync::Mutex;
+use core::time;
+
+const URL: &str = "https://kakuyomu.jp/works/100";
+
+///Responds with 503 and `Retry-After` until responses run out, then with 200
+struct Overloaded {
+    retry_after: Mutex<Vec<&'static str>>,
+}
+
+impl Overloaded {
+    fn new(retry_after: &[&'static str]) -> Self {
+        Self {
+            retry_after: Mutex::new(retry_after.iter().rev().copied().collect()),
+        }
+    }
+}
+
+impl http::Transport for Overloaded {
+    fn get(&self, _: &str, _: time::Duration) -> Result<http::Response, http::Error> {
+        Ok(match self.retry_after.lock().unwrap().pop() {
+            Some(retry_after) => http::Response {
+                status: 503,
+                headers: vec![("retry-after".to_owned(), retry_after.to_owned())],
+                body: Vec::new(),
+            },
+            None => http::Response {
+                status: 200,
+                headers: Vec::new(),
+                body: b"ok".to_vec(),
+            },
+        })
+    }
+}
+
+fn client(retry_after: &[&'static str]) -> http::Client {
+    http::Client::new().with_transport(Overloaded::new(retry_after)).with_retry(http::Retry {
+        retries: 3,
+        delay: time::Duration::from_millis(1),
+        max_delay: time::Duration::from_millis(1),
+        max_retry_after: time::Duration::from_secs(1),
+    })
+}
+
+#[test]
+fn should_honor_short_retry_after() {
+    let body: String = client(&["0", "Sun, 06 Nov 1994 08:49:37 GMT"]).get(URL).expect("retry");
+    assert_eq!(body, "ok");
+}
+
+#[test]
+fn should_fail_on_long_retry_after() {
+    let started = std::time::Instant::now();
+    match client(&["3600"]).get::<String>(URL) {
+        Err(http::Error::StatusFailed(503)) => (),
+        Err(error) => panic!("unexpected error: {}", error),
+        Ok(_) => panic!("request should fail"),
+    }
+    match client(&["Fri, 01 Jan 2100 00:00:00 GMT"]).get::<String>(URL) {
+        Err(http::Error::StatusFailed(503)) => (),
+        Err(error) => panic!("unexpected error: {}", error),
+        Ok(_) => panic!("request should fail"),
+    }
+    assert!(started.elapsed() < time::Duration::from_secs(1));
+}
+
+#[test]
+fn should_ignore_obsolete_date_format() {
+    //Falls back to backoff delay
+    let body: String = client(&["Sunday, 06-Nov-94 08:49:37 GMT"]).get(URL).expect("retry");
+    assert_eq!(body, "ok");
+}
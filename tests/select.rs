@@ -0,0 +1,65 @@
+use kakuyomu_dump::html;
+use kakuyomu_dump::select::{Date, Item, Selection};
+
+use core::num::NonZeroUsize;
+
+const WORK: &str = include_str!("fixtures/work.html");
+
+fn number(number: usize) -> Option<NonZeroUsize> {
+    NonZeroUsize::new(number)
+}
+
+fn select(selection: &str) -> Vec<usize> {
+    let index = html::Document::new(WORK).get_index().expect("index").expect("parse index");
+    let selection: Selection = selection.parse().expect("selection");
+    selection.select(&index).expect("select")
+}
+
+#[test]
+fn should_parse_selection() {
+    let selection: Selection = "1-10, 15,40-".parse().expect("selection");
+    assert_eq!(selection.items, [Item::Range(number(1), number(10)), Item::Range(number(15), number(15)), Item::Range(number(40), None)]);
+    assert_eq!(selection.to_string(), "1-10,15,40-");
+
+    assert_eq!("-5".parse(), Ok(Item::Range(None, number(5))));
+    assert_eq!("last:3".parse(), Ok(Item::Last(NonZeroUsize::new(3).unwrap())));
+    assert_eq!("id:1177354054883819763".parse(), Ok(Item::Episode("1177354054883819763".to_owned())));
+    assert_eq!("section: 第一章 ".parse(), Ok(Item::Section("第一章".to_owned())));
+}
+
+#[test]
+fn should_reject_invalid_selection() {
+    for text in ["last:0", "5-3", "section:", "id:", "id:12a", "-", "0", "1-x", ""] {
+        assert_eq!(text.parse::<Item>(), Err(()), "{}", text);
+    }
+    assert!(",".parse::<Selection>().is_err());
+    assert!("1,5-3".parse::<Selection>().is_err());
+}
+
+#[test]
+fn should_select_chapters() {
+    assert_eq!(select("1-10"), [0, 1, 2, 3]);
+    assert_eq!(select("4,1"), [0, 3]);
+    assert_eq!(select("last:1,-1"), [0, 3]);
+    assert_eq!(select("section:SecB"), [2, 3]);
+    assert_eq!(select("id:2"), [1]);
+}
+
+#[test]
+fn should_compare_dates_in_jst() {
+    let date: Date = "2025-01-01".parse().expect("date");
+
+    assert!(date.precedes("2024-12-31T15:00:00Z"));
+    assert!(!date.precedes("2024-12-31T14:59:59Z"));
+    assert!(date.follows("2025-01-01T14:59:59Z"));
+    assert!(!date.follows("2025-01-01T15:00:00Z"));
+    assert!(!date.precedes("invalid"));
+    assert_eq!(Date::of_timestamp("2024-12-31T15:00:00Z"), Some(date));
+}
+
+#[test]
+fn should_reject_invalid_date() {
+    assert!("2025-13-01".parse::<Date>().is_err());
+    assert!("2025-1-01".parse::<Date>().is_err());
+    assert!("2025-01-01-01".parse::<Date>().is_err());
+}
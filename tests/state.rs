@@ -0,0 +1,73 @@
+use kakuyomu_dump::{html, render, state};
+
+use std::{fs, path};
+
+const WORK: &str = include_str!("fixtures/work.html");
+
+fn state(name: &str, content: &str, chapters: &[(&str, u64)], end: u64) -> state::State {
+    let out = path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&out, content).expect("write output");
+    state::State {
+        novel: "100".to_owned(),
+        out,
+        format: render::Format::Markdown,
+        ruby: html::Ruby::Html,
+        sjis: false,
+        wrap: None,
+        chapters: chapters.iter().map(|(id, offset)| state::Chapter {
+            id: id.to_string(),
+            offset: *offset,
+        }).collect(),
+        end,
+    }
+}
+
+fn index() -> html::Index {
+    html::Document::new(WORK).get_index().expect("index").expect("parse index")
+}
+
+fn ids(state: &state::State) -> Vec<(&str, u64)> {
+    state.chapters.iter().map(|chapter| (chapter.id.as_str(), chapter.offset)).collect()
+}
+
+#[test]
+fn should_reorder_chapters() {
+    //Chapter 3 is retried after chapter 4, while trailing content stays in place
+    let mut state = state("reorder.md", "H\nA1\nA2\nA4\nA3\nEND\n", &[("1", 2), ("2", 5), ("4", 8), ("3", 11)], 14);
+    let out = state.out.clone();
+    state.reorder(&out, &index()).expect("reorder");
+
+    assert_eq!(fs::read_to_string(&out).expect("read output"), "H\nA1\nA2\nA3\nA4\nEND\n");
+    assert_eq!(ids(&state), [("1", 2), ("2", 5), ("3", 8), ("4", 11)]);
+    assert_eq!(state.end, 14);
+}
+
+#[test]
+fn should_keep_removed_chapter_after_preceding_one() {
+    let mut state = state("reorder_removed.md", "H\nA1\nA4\nX\nA2\n", &[("1", 2), ("4", 5), ("999", 8), ("2", 10)], 13);
+    let out = state.out.clone();
+    state.reorder(&out, &index()).expect("reorder");
+
+    assert_eq!(fs::read_to_string(&out).expect("read output"), "H\nA1\nA2\nA4\nX\n");
+    assert_eq!(ids(&state), [("1", 2), ("2", 5), ("4", 8), ("999", 11)]);
+}
+
+#[test]
+fn should_not_touch_ordered_output() {
+    let mut state = state("reorder_ordered.md", "H\nA1\nA2\n", &[("1", 2), ("2", 5)], 8);
+    let out = state.out.clone();
+    fs::remove_file(&out).expect("remove output");
+    //Output is not read, when chapters are already in order
+    state.reorder(&out, &index()).expect("reorder");
+    assert_eq!(ids(&state), [("1", 2), ("2", 5)]);
+}
+
+#[test]
+fn should_reject_invalid_offsets() {
+    let mut state = state("reorder_invalid.md", "H\nA2\n", &[("2", 2), ("1", 5)], 20);
+    let out = state.out.clone();
+    let error = state.reorder(&out, &index()).expect_err("reorder");
+
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(fs::read_to_string(&out).expect("read output"), "H\nA2\n");
+}